@@ -1,5 +1,7 @@
 extern crate embed_resource;
-use std::env;
+use std::{env, fs, path::Path};
+
+use serde_json::Value;
 
 fn main() {
     let target = env::var("TARGET").unwrap();
@@ -7,4 +9,210 @@ fn main() {
         // on windows we will set our game icon as icon for the executable
         embed_resource::compile("build/windows/icon.rc");
     }
+
+    validate_manifest();
+}
+
+/// Checks every file in the asset manifest exists, that the tilesets and
+/// images used by a group's files are in the group too, and that each level
+/// in `levels.json` has a group that loads its map and music and only uses
+/// exits and entrances its maps have, so a bad manifest fails the build
+/// instead of the game.
+fn validate_manifest() {
+    println!("cargo:rerun-if-changed=assets");
+
+    let assets = Path::new("assets");
+    let manifest = read_json(&assets.join("manifest.json"));
+    let levels = read_json(&assets.join("levels.json"));
+
+    let mut errors = vec![];
+
+    let common = manifest["common"].as_array().cloned().unwrap_or_default();
+    let level_groups = manifest["levels"].as_object().cloned().unwrap_or_default();
+
+    let groups = std::iter::once(("common", &common)).chain(
+        level_groups
+            .iter()
+            .map(|(name, group)| (name.as_str(), group.as_array().unwrap())),
+    );
+
+    for (name, group) in groups {
+        for file in group {
+            let file = file.as_str().unwrap();
+            if !assets.join(file).is_file() {
+                errors.push(format!("`{}` doesn't exist", file));
+                continue;
+            }
+
+            // Bevy doesn't wait for these before saying a file has loaded, so
+            // they have to be loaded as part of the group.
+            for reference in references(assets, file) {
+                let reference = Value::from(reference);
+                if !group.contains(&reference) && !common.contains(&reference) {
+                    errors.push(format!(
+                        "`{}` uses {} which isn't in the `{}` group",
+                        file, reference, name
+                    ));
+                }
+            }
+        }
+    }
+
+    for level in levels.as_array().unwrap() {
+        let name = level["name"].as_str().unwrap();
+        let map = &level["map"];
+
+        match level_groups.get(name).and_then(Value::as_array) {
+            Some(group) if group.contains(map) => {}
+            Some(_) => errors.push(format!("the `{}` group doesn't load its map {}", name, map)),
+            None => errors.push(format!("level `{}` has no group", name)),
+        }
+
+        if let Some(music) = level.get("music") {
+            let in_group = level_groups
+                .get(name)
+                .and_then(Value::as_array)
+                .is_some_and(|group| group.contains(music));
+            if !in_group && !common.contains(music) {
+                errors.push(format!(
+                    "level `{}` plays {} which isn't loaded",
+                    name, music
+                ));
+            }
+        }
+    }
+
+    // Maps are only loaded when their level is played, so check the exits
+    // and entrances in `levels.json` against them here.
+    for level in levels.as_array().unwrap() {
+        let name = level["name"].as_str().unwrap();
+        let exits = level["exits"].as_array().cloned().unwrap_or_default();
+        let map_exits = object_names(assets, &level["map"], "exit");
+
+        for exit in &exits {
+            let exit_name = exit["name"].as_str().unwrap();
+            if map_exits
+                .as_ref()
+                .is_some_and(|map_exits| !map_exits.iter().any(|map_exit| map_exit == exit_name))
+            {
+                errors.push(format!(
+                    "level `{}` has exit `{}` which isn't in its map",
+                    name, exit_name
+                ));
+            }
+
+            let Some(entrance) = exit["entrance"].as_str() else {
+                continue;
+            };
+            let Some(next) = levels
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|next| next["name"] == exit["level"])
+            else {
+                continue;
+            };
+            let spawns = object_names(assets, &next["map"], "spawn");
+            if spawns
+                .as_ref()
+                .is_some_and(|spawns| !spawns.iter().any(|spawn| spawn == entrance))
+            {
+                errors.push(format!(
+                    "level `{}`'s exit `{}` leads to entrance `{}` which isn't in level `{}`'s map",
+                    name,
+                    exit_name,
+                    entrance,
+                    next["name"].as_str().unwrap()
+                ));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        panic!("Invalid asset manifest:\n  {}", errors.join("\n  "));
+    }
+}
+
+/// The names of the objects of `class` in a map, or `None` if the map can't
+/// be read, which is reported separately.
+fn object_names(assets: &Path, map: &Value, class: &str) -> Option<Vec<String>> {
+    let path = assets.join(map.as_str()?);
+    if !path.is_file() {
+        return None;
+    }
+
+    let names = read_json(&path)["layers"]
+        .as_array()?
+        .iter()
+        .filter_map(|layer| layer["objects"].as_array())
+        .flatten()
+        .filter(|object| object["type"] == class || object["class"] == class)
+        .filter_map(|object| object["name"].as_str().map(str::to_string))
+        .collect();
+
+    Some(names)
+}
+
+/// The files a Tiled map, tileset or animation sheet loads, relative to the
+/// assets folder.
+fn references(assets: &Path, file: &str) -> Vec<String> {
+    let json = || read_json(&assets.join(file));
+
+    let sources = match Path::new(file).extension().and_then(|ext| ext.to_str()) {
+        Some("tmj") => {
+            let map = json();
+            let tilesets = map["tilesets"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|tileset| tileset["source"].as_str());
+            // Objects can reference files too, like the sounds they play.
+            let object_files = map["layers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|layer| layer["objects"].as_array())
+                .flatten()
+                .filter_map(|object| object["properties"].as_array())
+                .flatten()
+                .filter(|prop| prop["type"] == "file")
+                .filter_map(|prop| prop["value"].as_str());
+
+            tilesets.chain(object_files).map(str::to_string).collect()
+        }
+        Some("tsj") => vec![json()["image"].as_str().unwrap().to_string()],
+        // Animation sheets name their texture relative to the assets folder.
+        _ if file.ends_with(".anim.json") => {
+            return vec![json()["texture"].as_str().unwrap().to_string()];
+        }
+        _ => vec![],
+    };
+
+    sources.iter().map(|source| resolve(file, source)).collect()
+}
+
+/// Resolves a path relative to the file that references it, like the Tiled
+/// loaders do.
+fn resolve(file: &str, reference: &str) -> String {
+    let mut parts = file.split('/').collect::<Vec<_>>();
+    parts.pop();
+
+    for part in reference.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn read_json(path: &Path) -> Value {
+    let file = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Failed to read `{}`: {}", path.display(), err));
+    serde_json::from_str(&file)
+        .unwrap_or_else(|err| panic!("Failed to parse `{}`: {}", path.display(), err))
 }
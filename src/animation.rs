@@ -0,0 +1,304 @@
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{loader::LoadErrors, GameState};
+
+/// Spritesheets in the `animations` folder, loaded with the common assets.
+const ANIMATION_SHEETS: &[&str] = &["player"];
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AnimationSheet>()
+            .init_asset_loader::<AnimationSheetLoader>()
+            .add_event::<AnimationEvent>()
+            .add_system(setup_animations.in_schedule(OnExit(GameState::Loading)))
+            .add_system(animate_sprites.in_set(OnUpdate(GameState::Playing)));
+    }
+}
+
+/// How a clip behaves once it reaches its last frame.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    /// Stop on the last frame.
+    Once,
+}
+
+/// A named event fired when a clip reaches `frame`.
+#[derive(Deserialize, Clone)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+/// A sequence of frames in a spritesheet.
+#[derive(Deserialize, Clone)]
+pub struct AnimationClip {
+    /// Indices into the spritesheet's texture atlas.
+    pub frames: Vec<usize>,
+    pub fps: f32,
+    #[serde(default)]
+    pub mode: AnimationMode,
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+/// The on disk format of a spritesheet and its clips.
+#[derive(Deserialize)]
+struct AnimationSheetData {
+    /// Relative to the assets folder.
+    texture: String,
+    frame_width: u32,
+    frame_height: u32,
+    columns: usize,
+    rows: usize,
+    clips: HashMap<String, AnimationClip>,
+}
+
+impl AnimationSheetData {
+    /// Checks every clip can be played, so a mistake in a sheet is reported
+    /// when it's loaded instead of panicking once the clip is played.
+    fn validate(&self) -> Result<(), String> {
+        let frame_count = self.columns * self.rows;
+        let mut errors = vec![];
+
+        for (name, clip) in &self.clips {
+            if clip.frames.is_empty() {
+                errors.push(format!("clip `{}` has no frames", name));
+            }
+            if !(clip.fps.is_finite() && clip.fps > 0.0) {
+                errors.push(format!(
+                    "clip `{}` has an fps of {}, it must be above 0",
+                    name, clip.fps
+                ));
+            }
+            if let Some(frame) = clip.frames.iter().find(|&&frame| frame >= frame_count) {
+                errors.push(format!(
+                    "clip `{}` uses frame {} but the sheet only has {}",
+                    name, frame, frame_count
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            // Sorted since the clips are in a map.
+            errors.sort();
+            Err(errors.join(", "))
+        }
+    }
+}
+
+/// A spritesheet texture atlas along with the clips that can be played from
+/// it.
+#[derive(TypeUuid)]
+#[uuid = "5a5c4ad3-4e3c-4f3b-9b4e-2f7d6c1d8e90"]
+pub struct AnimationSheet {
+    pub atlas: Handle<TextureAtlas>,
+    pub clips: HashMap<String, AnimationClip>,
+}
+
+/// All the spritesheets loaded from the `animations` folder, keyed by their
+/// file name without the extension.
+#[derive(Resource, Default)]
+pub struct AnimationSheets(HashMap<String, Handle<AnimationSheet>>);
+
+impl AnimationSheets {
+    pub fn get(&self, name: &str) -> Option<Handle<AnimationSheet>> {
+        self.0.get(name).cloned()
+    }
+}
+
+/// Sent when an animated entity reaches a frame that has an event attached.
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub clip: String,
+    pub name: String,
+}
+
+/// Plays clips from an [`AnimationSheet`] on a `TextureAtlasSprite`.
+#[derive(Component)]
+pub struct Animator {
+    sheet: Handle<AnimationSheet>,
+    clip: String,
+    /// Index into the current clip's frames.
+    frame: usize,
+    timer: Timer,
+    finished: bool,
+}
+
+impl Animator {
+    pub fn new(sheet: Handle<AnimationSheet>, clip: &str) -> Self {
+        Self {
+            sheet,
+            clip: clip.to_string(),
+            frame: 0,
+            timer: Timer::default(),
+            finished: false,
+        }
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    /// Switches to `clip`, starting it from the beginning. Does nothing if
+    /// it's already playing.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+            self.frame = 0;
+            self.timer = Timer::default();
+            self.finished = false;
+        }
+    }
+
+    /// Whether a [`AnimationMode::Once`] clip has reached its last frame.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Loads spritesheets from `.anim.json` files, building the texture atlas
+/// for their texture and checking their clips can be played.
+struct AnimationSheetLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for AnimationSheetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world
+                .get_resource_or_insert_with(LoadErrors::default)
+                .clone(),
+        }
+    }
+}
+
+impl AssetLoader for AnimationSheetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let result = load_sheet(bytes, load_context);
+            self.errors.track(load_context.path(), result)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.json"]
+    }
+}
+
+fn load_sheet(bytes: &[u8], load_context: &mut LoadContext) -> Result<(), bevy::asset::Error> {
+    let data = serde_json::from_slice::<AnimationSheetData>(bytes)?;
+    data.validate().map_err(bevy::asset::Error::msg)?;
+
+    let texture_path = AssetPath::from(data.texture.as_str()).to_owned();
+    let atlas = TextureAtlas::from_grid(
+        load_context.get_handle(texture_path.clone()),
+        Vec2::new(data.frame_width as f32, data.frame_height as f32),
+        data.columns,
+        data.rows,
+        None,
+        None,
+    );
+
+    let sheet = AnimationSheet {
+        atlas: load_context.set_labeled_asset(
+            "atlas",
+            LoadedAsset::new(atlas).with_dependency(texture_path.clone()),
+        ),
+        clips: data.clips,
+    };
+
+    load_context.set_default_asset(LoadedAsset::new(sheet).with_dependency(texture_path));
+    Ok(())
+}
+
+fn setup_animations(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let sheets = ANIMATION_SHEETS
+        .iter()
+        .map(|name| {
+            let path = format!("animations/{}.anim.json", name);
+            (name.to_string(), asset_server.get_handle(path))
+        })
+        .collect();
+
+    commands.insert_resource(AnimationSheets(sheets));
+}
+
+fn animate_sprites(
+    time: Res<Time>,
+    sheets: Res<Assets<AnimationSheet>>,
+    mut animated: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
+    mut events: EventWriter<AnimationEvent>,
+) {
+    for (entity, mut animator, mut sprite) in &mut animated {
+        let Some(sheet) = sheets.get(&animator.sheet) else {
+            continue;
+        };
+        let Some(clip) = sheet.clips.get(&animator.clip) else {
+            warn!("Missing animation clip `{}`", animator.clip);
+            continue;
+        };
+
+        // A freshly started clip has a zero length timer, set it up and show
+        // its first frame.
+        if animator.timer.duration().is_zero() {
+            animator.timer = Timer::from_seconds(1.0 / clip.fps, TimerMode::Repeating);
+            sprite.index = clip.frames[0];
+            send_frame_events(entity, &animator.clip, clip, 0, &mut events);
+        }
+
+        if animator.finished {
+            continue;
+        }
+
+        animator.timer.tick(time.delta());
+
+        for _ in 0..animator.timer.times_finished_this_tick() {
+            let next = animator.frame + 1;
+
+            animator.frame = match clip.mode {
+                AnimationMode::Loop => next % clip.frames.len(),
+                AnimationMode::Once if next >= clip.frames.len() => {
+                    animator.finished = true;
+                    break;
+                }
+                AnimationMode::Once => next,
+            };
+
+            sprite.index = clip.frames[animator.frame];
+            send_frame_events(entity, &animator.clip, clip, animator.frame, &mut events);
+        }
+    }
+}
+
+fn send_frame_events(
+    entity: Entity,
+    clip_name: &str,
+    clip: &AnimationClip,
+    frame: usize,
+    events: &mut EventWriter<AnimationEvent>,
+) {
+    for event in clip.events.iter().filter(|event| event.frame == frame) {
+        events.send(AnimationEvent {
+            entity,
+            clip: clip_name.to_string(),
+            name: event.name.clone(),
+        });
+    }
+}
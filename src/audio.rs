@@ -0,0 +1,414 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::prelude::*;
+use serde_json::Value;
+
+use crate::{
+    animation::AnimationEvent,
+    checkpoint::{Collected, LevelObject},
+    health::Hurt,
+    level::CurrentLevel,
+    player::{Player, PlayerState, PlayerStateChanged},
+    settings::Settings,
+    tiled::{TiledMap, TiledObject},
+    GameState,
+};
+
+/// How long the last level's music takes to fade out while the next one's
+/// fades in.
+const CROSSFADE_SECONDS: f32 = 1.5;
+/// The class of point objects that play a sound heard from nearby.
+const SOUND_CLASS: &str = "sound";
+/// The class of rectangle objects that play a sound while the player is
+/// inside them.
+const AMBIENCE_CLASS: &str = "ambience";
+/// How far away a sound object can be heard from if it doesn't set `radius`.
+const DEFAULT_RADIUS: f32 = 128.0;
+/// How sharply a sound object gets quieter with distance if it doesn't set
+/// `falloff`, 1 fades it out evenly.
+const DEFAULT_FALLOFF: f32 = 1.0;
+/// How many seconds an ambience region takes to fade in or out if it doesn't
+/// set `fade`.
+const DEFAULT_FADE_SECONDS: f32 = 1.0;
+/// How long a level's ambient sounds take to fade out once it's despawned.
+const AMBIENT_STOP_SECONDS: f32 = 0.5;
+
+pub struct InternalAudioPlugin;
+
+impl Plugin for InternalAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(AudioPlugin)
+            .add_audio_channel::<Music>()
+            .add_audio_channel::<SoundEffects>()
+            .add_audio_channel::<Ambience>()
+            .init_resource::<SoundEffectHandles>()
+            .init_resource::<PlayingMusic>()
+            .init_resource::<AmbientInstances>()
+            .add_system(set_volumes.run_if(resource_changed::<Settings>()))
+            .add_system(play_level_music.run_if(
+                state_changed::<GameState>().or_else(resource_exists_and_changed::<CurrentLevel>()),
+            ))
+            // Not limited to playing, a fatal hit has already moved on to
+            // dying by the time its sound is played.
+            .add_systems((
+                play_movement_sounds,
+                play_animation_sounds,
+                play_hurt_sound,
+                play_pickup_sound,
+            ))
+            .add_systems((start_ambient_sounds, stop_ambient_sounds))
+            .add_system(update_ambient_sounds.in_set(OnUpdate(GameState::Playing)));
+    }
+}
+
+/// The channel level music is played on.
+#[derive(Resource)]
+struct Music;
+
+/// The channel gameplay sounds are played on.
+#[derive(Resource)]
+struct SoundEffects;
+
+/// The channel sounds placed in maps are played on. Each sound's volume is
+/// set on its own instance, so the channel's volume is left alone.
+#[derive(Resource)]
+struct Ambience;
+
+#[derive(Resource)]
+struct SoundEffectHandles {
+    footstep: Handle<AudioSource>,
+    jump: Handle<AudioSource>,
+    land: Handle<AudioSource>,
+    hurt: Handle<AudioSource>,
+    pickup: Handle<AudioSource>,
+}
+
+impl FromWorld for SoundEffectHandles {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        Self {
+            footstep: asset_server.load("audio/footstep.wav"),
+            jump: asset_server.load("audio/jump.wav"),
+            land: asset_server.load("audio/land.wav"),
+            hurt: asset_server.load("audio/hurt.wav"),
+            pickup: asset_server.load("audio/pickup.wav"),
+        }
+    }
+}
+
+/// The track the music channel is playing, if any.
+#[derive(Resource, Default)]
+struct PlayingMusic(Option<String>);
+
+fn set_volumes(
+    settings: Res<Settings>,
+    music: Res<AudioChannel<Music>>,
+    sound_effects: Res<AudioChannel<SoundEffects>>,
+) {
+    music.set_volume(settings.music_volume as f64);
+    sound_effects.set_volume(settings.sfx_volume as f64);
+}
+
+/// Crossfades to the current level's track when the level changes, and fades
+/// out when there's no level being played.
+fn play_level_music(
+    state: Res<State<GameState>>,
+    current: Option<Res<CurrentLevel>>,
+    asset_server: Res<AssetServer>,
+    music: Res<AudioChannel<Music>>,
+    mut playing: ResMut<PlayingMusic>,
+) {
+    let track = match state.0 {
+        GameState::Loading | GameState::Menu | GameState::LoadError => None,
+        _ => current.and_then(|current| current.data().music.clone()),
+    };
+
+    // Levels that share a track keep it playing through the transition.
+    if playing.0 == track {
+        return;
+    }
+
+    let crossfade = || {
+        AudioTween::new(
+            Duration::from_secs_f32(CROSSFADE_SECONDS),
+            AudioEasing::Linear,
+        )
+    };
+
+    // Commands run in order, so this only stops the track that was playing.
+    music.stop().fade_out(crossfade());
+
+    if let Some(track) = &track {
+        music
+            .play(asset_server.load(track.as_str()))
+            .looped()
+            .fade_in(crossfade());
+    }
+
+    playing.0 = track;
+}
+
+fn play_movement_sounds(
+    mut state_changed: EventReader<PlayerStateChanged>,
+    sounds: Res<SoundEffectHandles>,
+    sound_effects: Res<AudioChannel<SoundEffects>>,
+) {
+    for change in state_changed.iter() {
+        let landed = matches!(change.from, PlayerState::Falling | PlayerState::WallSliding)
+            && matches!(change.to, PlayerState::Idle | PlayerState::Walking);
+
+        if change.to == PlayerState::Jumping {
+            sound_effects.play(sounds.jump.clone());
+        } else if landed {
+            sound_effects.play(sounds.land.clone());
+        }
+    }
+}
+
+/// Plays the sounds attached to frames of the player's animations, like
+/// footsteps landing on the run cycle.
+fn play_animation_sounds(
+    mut animation_events: EventReader<AnimationEvent>,
+    players: Query<(), With<Player>>,
+    sounds: Res<SoundEffectHandles>,
+    sound_effects: Res<AudioChannel<SoundEffects>>,
+) {
+    for event in animation_events.iter() {
+        if !players.contains(event.entity) {
+            continue;
+        }
+
+        match event.name.as_str() {
+            "footstep" => {
+                sound_effects.play(sounds.footstep.clone());
+            }
+            name => warn!("No sound for `{}` in clip `{}`", name, event.clip),
+        }
+    }
+}
+
+fn play_hurt_sound(
+    mut hurt: EventReader<Hurt>,
+    players: Query<(), With<Player>>,
+    sounds: Res<SoundEffectHandles>,
+    sound_effects: Res<AudioChannel<SoundEffects>>,
+) {
+    if hurt.iter().any(|event| players.contains(event.target)) {
+        sound_effects.play(sounds.hurt.clone());
+    }
+}
+
+fn play_pickup_sound(
+    mut collected: EventReader<Collected>,
+    sounds: Res<SoundEffectHandles>,
+    sound_effects: Res<AudioChannel<SoundEffects>>,
+) {
+    for _ in collected.iter() {
+        sound_effects.play(sounds.pickup.clone());
+    }
+}
+
+/// A looping sound placed in a map, which gets louder as the player gets
+/// closer to it or moves into it.
+#[derive(Component)]
+pub struct AmbientSound {
+    sound: Handle<AudioSource>,
+    /// The sound's volume at its loudest, before the sound effects volume is
+    /// applied.
+    volume: f32,
+    shape: AmbientShape,
+}
+
+enum AmbientShape {
+    /// Heard within `radius` pixels, getting quieter further away and panned
+    /// toward the side it's on.
+    Emitter { radius: f32, falloff: f32 },
+    /// Heard while the player is inside it, fading in and out over `fade`
+    /// seconds as they enter and leave.
+    Region {
+        half_size: Vec2,
+        fade: f32,
+        level: f32,
+    },
+}
+
+/// The instances playing each ambient sound entity.
+#[derive(Resource, Default)]
+struct AmbientInstances(HashMap<Entity, Handle<AudioInstance>>);
+
+fn property<'a>(object: &'a TiledObject, name: &str) -> Option<&'a Value> {
+    object
+        .properties
+        .iter()
+        .find(|prop| prop.name == name)
+        .map(|prop| &prop.value)
+}
+
+/// Spawns the entity for a sound or ambience object. Returns `false` if
+/// `object` isn't one of those.
+pub fn spawn_sound_object(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    map: &TiledMap,
+    object: &TiledObject,
+) -> bool {
+    if object.class != SOUND_CLASS && object.class != AMBIENCE_CLASS {
+        return false;
+    }
+
+    let Some(sound) = property(object, "sound").and_then(Value::as_str) else {
+        warn!(
+            "Sound `{}` ({}) has no `sound` property",
+            object.name, object.id
+        );
+        return true;
+    };
+    let number = |name, default| {
+        property(object, name)
+            .and_then(Value::as_f64)
+            .map_or(default, |value| value as f32)
+    };
+
+    // Neither can be zero or less, the sound would be at full volume
+    // everywhere or get infinitely loud at the edge.
+    let positive = |name, default| {
+        let value = number(name, default);
+        if value > 0.0 && value.is_finite() {
+            value
+        } else {
+            warn!(
+                "Sound `{}` ({}) has `{}` {}, it must be more than 0",
+                object.name, object.id, name, value
+            );
+            default
+        }
+    };
+
+    let (shape, center) = if object.class == SOUND_CLASS {
+        let shape = AmbientShape::Emitter {
+            radius: positive("radius", DEFAULT_RADIUS),
+            falloff: positive("falloff", DEFAULT_FALLOFF),
+        };
+        (shape, map.to_world(object.x, object.y))
+    } else {
+        let shape = AmbientShape::Region {
+            half_size: Vec2::new(object.width, object.height) / 2.0,
+            fade: number("fade", DEFAULT_FADE_SECONDS),
+            level: 0.0,
+        };
+        let center = map.to_world(
+            object.x + object.width / 2.0,
+            object.y + object.height / 2.0,
+        );
+        (shape, center)
+    };
+
+    commands.spawn((
+        LevelObject(object.id),
+        AmbientSound {
+            sound: asset_server.load(sound),
+            volume: number("volume", 1.0),
+            shape,
+        },
+        TransformBundle::from_transform(Transform::from_translation(center.extend(0.0))),
+    ));
+
+    true
+}
+
+/// Starts new ambient sounds silent, they're faded up once the player is
+/// near.
+fn start_ambient_sounds(
+    sounds: Query<(Entity, &AmbientSound), Added<AmbientSound>>,
+    ambience: Res<AudioChannel<Ambience>>,
+    mut instances: ResMut<AmbientInstances>,
+) {
+    for (entity, sound) in sounds.iter() {
+        let instance = ambience
+            .play(sound.sound.clone())
+            .looped()
+            .with_volume(0.0)
+            .handle();
+        instances.0.insert(entity, instance);
+    }
+}
+
+/// Sets each ambient sound's volume and panning from where the player is.
+fn update_ambient_sounds(
+    player: Query<&Transform, With<Player>>,
+    mut sounds: Query<(Entity, &Transform, &mut AmbientSound), Without<Player>>,
+    instances: Res<AmbientInstances>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    let listener = player.translation.truncate();
+
+    for (entity, transform, mut sound) in sounds.iter_mut() {
+        let offset = transform.translation.truncate() - listener;
+
+        let (loudness, panning) = match &mut sound.shape {
+            AmbientShape::Emitter { radius, falloff } => {
+                let loudness = (1.0 - offset.length() / *radius)
+                    .clamp(0.0, 1.0)
+                    .powf(*falloff);
+                // Hard left is 0 and hard right is 1.
+                let panning = 0.5 + (offset.x / *radius).clamp(-1.0, 1.0) * 0.5;
+                (loudness, panning)
+            }
+            AmbientShape::Region {
+                half_size,
+                fade,
+                level,
+            } => {
+                let inside = offset.abs().cmple(*half_size).all();
+                let step = time.delta_seconds() / fade.max(f32::EPSILON);
+                *level = if inside {
+                    (*level + step).min(1.0)
+                } else {
+                    (*level - step).max(0.0)
+                };
+                (*level, 0.5)
+            }
+        };
+
+        let Some(instance) = instances
+            .0
+            .get(&entity)
+            .and_then(|handle| audio_instances.get_mut(handle))
+        else {
+            continue;
+        };
+
+        let volume = loudness * sound.volume * settings.sfx_volume;
+        instance.set_volume(volume as f64, AudioTween::default());
+        instance.set_panning(panning as f64, AudioTween::default());
+    }
+}
+
+/// Fades out the sounds of ambient sound entities that have been despawned,
+/// like when the level changes.
+fn stop_ambient_sounds(
+    sounds: Query<(), With<AmbientSound>>,
+    mut instances: ResMut<AmbientInstances>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    instances.0.retain(|entity, handle| {
+        if sounds.contains(*entity) {
+            return true;
+        }
+
+        if let Some(instance) = audio_instances.get_mut(handle) {
+            instance.stop(AudioTween::new(
+                Duration::from_secs_f32(AMBIENT_STOP_SECONDS),
+                AudioEasing::Linear,
+            ));
+        }
+        false
+    });
+}
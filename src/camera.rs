@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use crate::{
+    level::MapBounds,
+    physics,
+    player::{Player, PlayerMovement},
+    GameState,
+};
+
+/// How far the player can move from the camera's focus before it starts
+/// following, as half the size of the deadzone.
+const DEADZONE: Vec2 = Vec2::new(24.0, 40.0);
+/// How far ahead of the player the camera looks in the direction they're
+/// facing.
+const LOOK_AHEAD: f32 = 48.0;
+/// How quickly the camera catches up with where it's aiming, higher is
+/// snappier.
+const FOLLOW_SPEED: f32 = 6.0;
+/// How quickly the look-ahead swings across when the player turns around.
+const LOOK_AHEAD_SPEED: f32 = 2.0;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_camera.in_schedule(OnEnter(GameState::Loading)))
+            .add_system(
+                follow_player
+                    .after(physics::update)
+                    .in_set(OnUpdate(GameState::Playing)),
+            );
+    }
+}
+
+/// The camera that follows the player around the level.
+#[derive(Component, Default)]
+pub struct GameCamera {
+    /// The point the deadzone is centered on, which only moves when the
+    /// player pushes against the deadzone's edges.
+    focus: Vec2,
+    /// How far the camera is currently looking ahead horizontally.
+    look_ahead: f32,
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), GameCamera::default()));
+}
+
+/// The fraction of the remaining distance to cover this frame when easing
+/// towards a target at `speed`, independent of the frame rate.
+fn smoothing(speed: f32, delta: f32) -> f32 {
+    1.0 - (-speed * delta).exp()
+}
+
+/// Keeps `position` far enough inside `min..max` that none of the view, which
+/// extends `half_view` either side of it, is outside. The view is centered if
+/// it's bigger than the range.
+fn clamp_axis(position: f32, min: f32, max: f32, half_view: f32) -> f32 {
+    if max - min <= half_view * 2.0 {
+        (min + max) / 2.0
+    } else {
+        position.clamp(min + half_view, max - half_view)
+    }
+}
+
+fn follow_player(
+    player: Query<(&Transform, &PlayerMovement, Ref<Player>)>,
+    mut camera: Query<(&mut Transform, &mut GameCamera, &OrthographicProjection), Without<Player>>,
+    bounds: Res<MapBounds>,
+    time: Res<Time>,
+) {
+    let Ok((player_transform, movement, player)) = player.get_single() else {
+        return;
+    };
+    let (mut transform, mut camera, projection) = camera.single_mut();
+
+    let position = player_transform.translation.truncate();
+    // A new player means a level was just spawned or respawned, so jump
+    // straight there rather than panning across the map.
+    let snap = player.is_added();
+
+    if snap {
+        camera.focus = position;
+        camera.look_ahead = movement.facing * LOOK_AHEAD;
+    } else {
+        let offset = position - camera.focus;
+        camera.focus += offset - offset.clamp(-DEADZONE, DEADZONE);
+
+        let look_ahead = movement.facing * LOOK_AHEAD;
+        camera.look_ahead +=
+            (look_ahead - camera.look_ahead) * smoothing(LOOK_AHEAD_SPEED, time.delta_seconds());
+    }
+
+    let aim = camera.focus + Vec2::new(camera.look_ahead, 0.0);
+    let half_view = projection.area.size() / 2.0;
+    let target = Vec2::new(
+        clamp_axis(aim.x, bounds.0.min.x, bounds.0.max.x, half_view.x),
+        clamp_axis(aim.y, bounds.0.min.y, bounds.0.max.y, half_view.y),
+    );
+
+    let current = transform.translation.truncate();
+    let next = if snap {
+        target
+    } else {
+        current + (target - current) * smoothing(FOLLOW_SPEED, time.delta_seconds())
+    };
+    transform.translation = next.extend(transform.translation.z);
+}
@@ -0,0 +1,278 @@
+use bevy::{prelude::*, sprite::collide_aabb, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    level::CurrentLevel,
+    physics::{Collider, Solid},
+    player::Player,
+    tiled::{TiledMap, TiledObject},
+    GameState,
+};
+
+const CHECKPOINT_COLOR: Color = Color::GRAY;
+const ACTIVE_CHECKPOINT_COLOR: Color = Color::LIME_GREEN;
+const COLLECTIBLE_COLOR: Color = Color::GOLD;
+const DOOR_COLOR: Color = Color::MAROON;
+/// The class of objects the player respawns at after touching.
+const CHECKPOINT_CLASS: &str = "checkpoint";
+/// The class of objects the player can pick up.
+pub const COLLECTIBLE_CLASS: &str = "collectible";
+/// The class of objects that open once their key has been collected.
+const DOOR_CLASS: &str = "door";
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Collected>()
+            .init_resource::<LevelObjectState>()
+            .init_resource::<ActiveCheckpoint>()
+            .add_systems(
+                (touch_checkpoints, collect_items, open_doors).in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(restore_checkpoint.in_schedule(OnExit(GameState::Dying)));
+    }
+}
+
+/// Any object spawned from a map's object layers, holding its Tiled object
+/// id.
+#[derive(Component)]
+pub struct LevelObject(pub u32);
+
+/// Records the player's respawn location and the state of the level when
+/// touched.
+#[derive(Component)]
+pub struct Checkpoint;
+
+#[derive(Component)]
+pub struct Collectible;
+
+/// Sent when the player picks up a collectible.
+pub struct Collected;
+
+/// A solid door that opens when the player touches it after collecting its
+/// key.
+#[derive(Component)]
+pub struct Door {
+    /// The object id of the collectible that opens this door.
+    pub key: u32,
+}
+
+/// Which of the current level's objects have been collected or opened.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct LevelObjectState {
+    pub collected: HashSet<u32>,
+    pub opened: HashSet<u32>,
+}
+
+impl LevelObjectState {
+    /// Whether the object has been collected or opened and shouldn't be
+    /// spawned again.
+    pub fn is_removed(&self, id: u32) -> bool {
+        self.collected.contains(&id) || self.opened.contains(&id)
+    }
+}
+
+/// A snapshot of the level taken when a checkpoint was touched.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckpointData {
+    pub level: String,
+    pub id: u32,
+    /// Where the player respawns, this is the position of their feet.
+    pub position: Vec2,
+    pub state: LevelObjectState,
+}
+
+/// The last checkpoint the player touched, if any.
+#[derive(Resource, Default)]
+pub struct ActiveCheckpoint(pub Option<CheckpointData>);
+
+impl ActiveCheckpoint {
+    /// Gets the checkpoint if it belongs to `level`.
+    pub fn for_level(&self, level: &str) -> Option<&CheckpointData> {
+        self.0
+            .as_ref()
+            .filter(|checkpoint| checkpoint.level == level)
+    }
+}
+
+/// Spawns the entity for a checkpoint, collectible or door object. Returns
+/// `false` if `object` isn't one of those.
+pub fn spawn_object(
+    commands: &mut Commands,
+    map: &TiledMap,
+    object: &TiledObject,
+    state: &LevelObjectState,
+    checkpoint: Option<&CheckpointData>,
+) -> bool {
+    if !matches!(
+        object.class.as_str(),
+        CHECKPOINT_CLASS | COLLECTIBLE_CLASS | DOOR_CLASS
+    ) {
+        return false;
+    }
+
+    if state.is_removed(object.id) {
+        return true;
+    }
+
+    // Point objects don't have a size so give them one tile's worth.
+    let size = if object.point {
+        Vec2::new(map.tile_width as f32, map.tile_height as f32)
+    } else {
+        Vec2::new(object.width, object.height)
+    };
+    let center = if object.point {
+        map.to_world(object.x, object.y)
+    } else {
+        map.to_world(
+            object.x + object.width / 2.0,
+            object.y + object.height / 2.0,
+        )
+    };
+
+    let sprite = |color: Color| SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform::from_translation(center.extend(50.0)),
+        ..default()
+    };
+
+    let bundle = (LevelObject(object.id), Collider { size });
+
+    match object.class.as_str() {
+        CHECKPOINT_CLASS => {
+            let active = checkpoint.is_some_and(|checkpoint| checkpoint.id == object.id);
+            let color = if active {
+                ACTIVE_CHECKPOINT_COLOR
+            } else {
+                CHECKPOINT_COLOR
+            };
+
+            commands.spawn((bundle, Checkpoint, sprite(color)));
+        }
+        COLLECTIBLE_CLASS => {
+            commands.spawn((bundle, Collectible, sprite(COLLECTIBLE_COLOR)));
+        }
+        _ => {
+            let key = object
+                .properties
+                .iter()
+                .find(|prop| prop.name == "key")
+                .and_then(|prop| prop.value.as_u64());
+
+            match key {
+                Some(key) => {
+                    commands.spawn((bundle, Door { key: key as u32 }, Solid, sprite(DOOR_COLOR)));
+                }
+                None => warn!(
+                    "Door `{}` ({}) has no `key` property",
+                    object.name, object.id
+                ),
+            }
+        }
+    }
+
+    true
+}
+
+fn touching(a: &Transform, a_collider: &Collider, b: &Transform, b_collider: &Collider) -> bool {
+    // Grow the first collider a little so touching solids counts too.
+    collide_aabb::collide(
+        a.translation,
+        a_collider.size + Vec2::splat(2.0),
+        b.translation,
+        b_collider.size,
+    )
+    .is_some()
+}
+
+fn touch_checkpoints(
+    player: Query<(&Transform, &Collider), With<Player>>,
+    mut checkpoints: Query<(&LevelObject, &Transform, &Collider, &mut Sprite), With<Checkpoint>>,
+    current_level: Res<CurrentLevel>,
+    state: Res<LevelObjectState>,
+    mut active: ResMut<ActiveCheckpoint>,
+) {
+    let (player_transform, player_collider) = player.single();
+
+    let active_id = active
+        .for_level(current_level.name())
+        .map(|checkpoint| checkpoint.id);
+
+    for (object, transform, collider, mut sprite) in &mut checkpoints {
+        if active_id == Some(object.0) {
+            continue;
+        }
+
+        if touching(player_transform, player_collider, transform, collider) {
+            info!("Reached checkpoint {}", object.0);
+
+            // Stand the player on the bottom of the checkpoint when respawning.
+            let position = transform.translation.truncate() - Vec2::new(0.0, collider.size.y / 2.0);
+
+            active.0 = Some(CheckpointData {
+                level: current_level.name().to_string(),
+                id: object.0,
+                position,
+                state: state.clone(),
+            });
+            sprite.color = ACTIVE_CHECKPOINT_COLOR;
+        } else if sprite.color != CHECKPOINT_COLOR {
+            // Only the active checkpoint stays lit.
+            sprite.color = CHECKPOINT_COLOR;
+        }
+    }
+}
+
+fn collect_items(
+    mut commands: Commands,
+    player: Query<(&Transform, &Collider), With<Player>>,
+    collectibles: Query<(Entity, &LevelObject, &Transform, &Collider), With<Collectible>>,
+    mut state: ResMut<LevelObjectState>,
+    mut collected: EventWriter<Collected>,
+) {
+    let (player_transform, player_collider) = player.single();
+
+    for (entity, object, transform, collider) in &collectibles {
+        if touching(player_transform, player_collider, transform, collider) {
+            state.collected.insert(object.0);
+            collected.send(Collected);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn open_doors(
+    mut commands: Commands,
+    player: Query<(&Transform, &Collider), With<Player>>,
+    doors: Query<(Entity, &LevelObject, &Door, &Transform, &Collider)>,
+    mut state: ResMut<LevelObjectState>,
+) {
+    let (player_transform, player_collider) = player.single();
+
+    for (entity, object, door, transform, collider) in &doors {
+        if state.collected.contains(&door.key)
+            && touching(player_transform, player_collider, transform, collider)
+        {
+            state.opened.insert(object.0);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Puts the level back how it was when the checkpoint was reached, or how it
+/// started if no checkpoint was reached.
+fn restore_checkpoint(
+    current_level: Res<CurrentLevel>,
+    active: Res<ActiveCheckpoint>,
+    mut state: ResMut<LevelObjectState>,
+) {
+    *state = active
+        .for_level(current_level.name())
+        .map(|checkpoint| checkpoint.state.clone())
+        .unwrap_or_default();
+}
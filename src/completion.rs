@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    input::{Action, ActionState},
+    level::{LevelProgress, LevelStats, Levels},
+    GameState,
+};
+
+pub struct CompletionPlugin;
+
+impl Plugin for CompletionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_completion_screen.in_schedule(OnEnter(GameState::Complete)))
+            .add_system(return_to_menu.in_set(OnUpdate(GameState::Complete)))
+            .add_system(despawn_completion_screen.in_schedule(OnExit(GameState::Complete)));
+    }
+}
+
+#[derive(Component)]
+struct CompletionScreen;
+
+/// Formats a duration as minutes and seconds, e.g. `1:05.3`.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs_f32();
+    format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+fn spawn_completion_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+    stats: Res<LevelStats>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    // Only the levels that were played, in the order they're listed.
+    let played = levels
+        .iter()
+        .filter_map(|level| {
+            let time = stats.time.get(&level.name)?;
+            let deaths = stats.deaths.get(&level.name).copied().unwrap_or_default();
+            Some((level.name.as_str(), *time, deaths))
+        })
+        .collect::<Vec<_>>();
+
+    let total_time = played.iter().map(|(_, time, _)| *time).sum::<Duration>();
+    let total_deaths = played.iter().map(|(_, _, deaths)| deaths).sum::<u32>();
+
+    commands
+        .spawn((
+            CompletionScreen,
+            NodeBundle {
+                style: Style {
+                    size: Size::all(Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::height(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Thanks for playing!",
+                TextStyle {
+                    font_size: 40.0,
+                    ..style.clone()
+                },
+            ));
+
+            for (name, time, deaths) in played.iter() {
+                parent.spawn(TextBundle::from_section(
+                    format!("{}: {}, {} deaths", name, format_time(*time), deaths),
+                    style.clone(),
+                ));
+            }
+
+            parent.spawn(TextBundle::from_section(
+                format!(
+                    "Total: {}, {} deaths",
+                    format_time(total_time),
+                    total_deaths
+                ),
+                TextStyle {
+                    font_size: 28.0,
+                    ..style.clone()
+                },
+            ));
+
+            parent.spawn(TextBundle::from_section(
+                "Press confirm to return to the menu",
+                TextStyle {
+                    color: Color::GRAY,
+                    ..style
+                },
+            ));
+        });
+}
+
+fn return_to_menu(
+    actions: Res<ActionState>,
+    mut progress: LevelProgress,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Confirm) {
+        // The game's finished so there's nothing to continue.
+        progress.reset();
+        next_state.set(GameState::Menu);
+    }
+}
+
+fn despawn_completion_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<CompletionScreen>>,
+) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,63 @@
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Name of the folder our config files live in.
+pub const APP_DIR: &str = "bevy_game";
+
+/// The platform specific directory config files are stored in, or `None` if
+/// it can't be determined.
+fn config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library/Application Support"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    base.map(|base| base.join(APP_DIR))
+}
+
+/// Reads and deserializes the config file `name`. Returns `None` if it
+/// doesn't exist yet or can't be read, in which case defaults should be used.
+pub fn load<T: DeserializeOwned>(name: &str) -> Option<T> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+
+    let path = config_dir()?.join(name);
+    let contents = fs::read_to_string(&path).ok()?;
+
+    match serde_json::from_str(&contents) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Ignoring invalid config file '{}': {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Serializes `value` to the config file `name`, logging if that fails.
+pub fn save<T: Serialize>(name: &str, value: &T) {
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+
+    let Some(dir) = config_dir() else {
+        warn!("Couldn't find a config directory to save '{}' to", name);
+        return;
+    };
+
+    let result = fs::create_dir_all(&dir)
+        .map_err(|err| err.to_string())
+        .and_then(|_| serde_json::to_string_pretty(value).map_err(|err| err.to_string()))
+        .and_then(|json| fs::write(dir.join(name), json).map_err(|err| err.to_string()));
+
+    if let Err(err) = result {
+        error!("Failed to save config file '{}': {}", name, err);
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::{
+    input::{Action, InputMap, Rebinding},
+    menu::{self, actions_in, MenuAction, MenuEvent, MenuScreen, MenuStack},
+};
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        // Reachable from the pause menu too, so not limited to a state.
+        app.add_systems((handle_controls, update_controls_labels).chain());
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum ControlsItem {
+    /// One of `action`'s bindings, `slot` is the one that's rebound. A slot
+    /// past its last binding adds a new one.
+    Action {
+        action: Action,
+        slot: usize,
+    },
+    Reset,
+    Back,
+}
+
+impl ControlsItem {
+    /// Lists the bindings with the selected one in brackets, e.g. `Jump:
+    /// [Space], South`.
+    fn label(self, input_map: &InputMap, rebinding: &Rebinding) -> String {
+        let (action, slot) = match self {
+            ControlsItem::Action { action, slot } => (action, slot),
+            ControlsItem::Reset => return "Reset to Defaults".to_string(),
+            ControlsItem::Back => return "Back".to_string(),
+        };
+
+        if rebinding.action == Some(action) {
+            return format!("{}: Press an input...", action.label());
+        }
+
+        let bindings = input_map
+            .bindings
+            .get(&action)
+            .map_or(&[][..], Vec::as_slice);
+        let mut labels = bindings
+            .iter()
+            .map(ToString::to_string)
+            .chain(std::iter::once("Add".to_string()))
+            .collect::<Vec<_>>();
+        if let Some(selected) = labels.get_mut(slot) {
+            *selected = format!("[{}]", selected);
+        }
+        // Only offer to add a binding when it's selected.
+        if slot < bindings.len() {
+            labels.pop();
+        }
+
+        format!("{}: {}", action.label(), labels.join(", "))
+    }
+}
+
+pub fn spawn_controls_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    input_map: &InputMap,
+    rebinding: &Rebinding,
+) {
+    menu::spawn_menu(commands, asset_server, "Controls", |items| {
+        let actions = Action::REBINDABLE
+            .iter()
+            .map(|&action| ControlsItem::Action { action, slot: 0 });
+
+        for item in actions.chain([ControlsItem::Reset, ControlsItem::Back]) {
+            items.add(item.label(input_map, rebinding), true, item);
+        }
+    });
+}
+
+fn handle_controls(
+    mut events: EventReader<MenuEvent>,
+    mut items: Query<&mut ControlsItem>,
+    mut stack: ResMut<MenuStack>,
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    // Actions are ignored while an input is captured, but clicking still
+    // works, and the click that finished a capture shouldn't start another.
+    let capturing = rebinding.action.is_some() || rebinding.is_changed();
+
+    for action in actions_in(&mut events, MenuScreen::Controls) {
+        if capturing {
+            continue;
+        }
+
+        let item = match action {
+            MenuAction::Back => {
+                stack.0.pop();
+                continue;
+            }
+            MenuAction::Confirm(item) | MenuAction::Adjust(item, _) => item,
+        };
+        let Ok(mut item) = items.get_mut(item) else {
+            continue;
+        };
+
+        match (*item, action) {
+            (ControlsItem::Action { action, slot }, MenuAction::Confirm(_)) => {
+                rebinding.action = Some(action);
+                rebinding.slot = slot;
+            }
+            (ControlsItem::Action { action, slot }, MenuAction::Adjust(_, direction)) => {
+                let count = input_map.bindings.get(&action).map_or(0, Vec::len);
+                let slot = slot.saturating_add_signed(direction as isize).min(count);
+
+                *item = ControlsItem::Action { action, slot };
+            }
+            (ControlsItem::Reset, MenuAction::Confirm(_)) => {
+                input_map.bindings = InputMap::default().bindings;
+
+                for mut item in &mut items {
+                    if let ControlsItem::Action { slot, .. } = &mut *item {
+                        *slot = 0;
+                    }
+                }
+            }
+            (ControlsItem::Back, MenuAction::Confirm(_)) => {
+                stack.0.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_controls_labels(
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    mut items: Query<(Ref<ControlsItem>, &mut Text)>,
+) {
+    let all_changed = input_map.is_changed() || rebinding.is_changed();
+
+    for (item, mut text) in &mut items {
+        if all_changed || item.is_changed() {
+            text.sections[0].value = item.label(&input_map, &rebinding);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::{prelude::*, sprite::collide_aabb};
+
+use crate::{
+    level::{CurrentLevel, LevelStats, MapBounds},
+    physics::Collider,
+    player::Player,
+    GameState,
+};
+
+/// How long the player can't be hurt again after taking damage.
+const INVINCIBILITY_SECONDS: f32 = 1.5;
+/// How often the player's sprite toggles while invincible.
+const FLICKER_SECONDS: f32 = 0.1;
+/// How long the death sequence plays before the player respawns.
+const DEATH_SECONDS: f32 = 1.0;
+/// How far below the map the player can fall before they die.
+const FALL_MARGIN: f32 = 64.0;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<Hurt>()
+            .add_systems(
+                (
+                    hazard_contact,
+                    out_of_bounds,
+                    apply_damage,
+                    tick_invincibility,
+                )
+                    .chain()
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(start_death.in_schedule(OnEnter(GameState::Dying)))
+            .add_system(play_death.in_set(OnUpdate(GameState::Dying)));
+    }
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub current: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max }
+    }
+}
+
+/// Hurts anything with [`Health`] that touches it, used for hazard tiles.
+#[derive(Component)]
+pub struct Hazard {
+    pub damage: u32,
+}
+
+/// Protects an entity from damage until the timer runs out.
+#[derive(Component)]
+pub struct Invincible {
+    timer: Timer,
+    flicker: Timer,
+}
+
+impl Default for Invincible {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(INVINCIBILITY_SECONDS, TimerMode::Once),
+            flicker: Timer::from_seconds(FLICKER_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageSource {
+    Hazard,
+    /// Fell out of the bottom of the map, this is always fatal.
+    OutOfBounds,
+}
+
+/// Sent to hurt an entity with [`Health`].
+pub struct Damage {
+    pub target: Entity,
+    pub amount: u32,
+    pub source: DamageSource,
+}
+
+/// Sent when an entity actually loses health, after invincibility has been
+/// taken into account.
+pub struct Hurt {
+    pub target: Entity,
+}
+
+#[derive(Resource)]
+struct DeathTimer(Timer);
+
+type DamageTargets<'w, 's, 'a> =
+    Query<'w, 's, (Entity, &'a Transform, &'a Collider), (With<Health>, Without<Invincible>)>;
+
+fn hazard_contact(
+    targets: DamageTargets,
+    hazards: Query<(&Transform, &Collider, &Hazard)>,
+    mut damage: EventWriter<Damage>,
+) {
+    for (entity, transform, collider) in &targets {
+        // Hazards are usually solid so the target can never overlap them,
+        // grow its collider a little so touching one still counts.
+        let size = collider.size + Vec2::splat(2.0);
+
+        let hazard = hazards
+            .iter()
+            .find(|(hazard_transform, hazard_collider, _)| {
+                collide_aabb::collide(
+                    transform.translation,
+                    size,
+                    hazard_transform.translation,
+                    hazard_collider.size,
+                )
+                .is_some()
+            });
+
+        if let Some((_, _, hazard)) = hazard {
+            damage.send(Damage {
+                target: entity,
+                amount: hazard.damage,
+                source: DamageSource::Hazard,
+            });
+        }
+    }
+}
+
+fn out_of_bounds(
+    bounds: Res<MapBounds>,
+    player: Query<(Entity, &Transform, &Health), With<Player>>,
+    mut damage: EventWriter<Damage>,
+) {
+    let (entity, transform, health) = player.single();
+
+    if transform.translation.y < bounds.0.min.y - FALL_MARGIN {
+        damage.send(Damage {
+            target: entity,
+            amount: health.current,
+            source: DamageSource::OutOfBounds,
+        });
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut events: EventReader<Damage>,
+    mut targets: Query<(&mut Health, Option<&Invincible>, Option<&Player>)>,
+    current_level: Res<CurrentLevel>,
+    mut stats: ResMut<LevelStats>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut hurt: EventWriter<Hurt>,
+) {
+    for event in events.iter() {
+        let Ok((mut health, invincible, is_player)) = targets.get_mut(event.target) else {
+            continue;
+        };
+
+        // Dead things can't be hurt again, and falling out of the map should
+        // always be fatal.
+        if health.current == 0
+            || (invincible.is_some() && event.source != DamageSource::OutOfBounds)
+        {
+            continue;
+        }
+
+        health.current = health.current.saturating_sub(event.amount);
+        hurt.send(Hurt {
+            target: event.target,
+        });
+
+        if health.current > 0 {
+            commands.entity(event.target).insert(Invincible::default());
+        } else if is_player.is_some() {
+            info!("Player died from {:?}", event.source);
+
+            stats.record_death(current_level.name());
+            next_state.set(GameState::Dying);
+        } else {
+            commands.entity(event.target).despawn_recursive();
+        }
+    }
+}
+
+fn tick_invincibility(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut invincible: Query<(Entity, &mut Invincible, &mut Visibility)>,
+) {
+    for (entity, mut invincible, mut visibility) in &mut invincible {
+        invincible.timer.tick(time.delta());
+        invincible.flicker.tick(time.delta());
+
+        if invincible.timer.finished() {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<Invincible>();
+        } else if invincible.flicker.just_finished() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
+
+fn start_death(mut commands: Commands) {
+    commands.insert_resource(DeathTimer(Timer::from_seconds(
+        DEATH_SECONDS,
+        TimerMode::Once,
+    )));
+}
+
+/// Fades and spins the player out, then respawns them by re-entering
+/// [`GameState::Playing`].
+fn play_death(
+    time: Res<Time>,
+    mut timer: ResMut<DeathTimer>,
+    mut player: Query<(&mut Transform, &mut TextureAtlasSprite, &mut Visibility), With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    timer.0.tick(time.delta());
+
+    // Respawn even if the player's gone, rather than getting stuck here.
+    if timer.0.finished() {
+        next_state.set(GameState::Playing);
+    }
+
+    let Ok((mut transform, mut sprite, mut visibility)) = player.get_single_mut() else {
+        return;
+    };
+    let progress = timer.0.percent();
+
+    *visibility = Visibility::Inherited;
+    sprite.color.set_a(1.0 - progress);
+    transform.rotation = Quat::from_rotation_z(progress * std::f32::consts::TAU);
+}
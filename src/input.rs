@@ -0,0 +1,516 @@
+use std::{collections::BTreeMap, fmt};
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::InputSystem,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// The file the player's bindings are persisted to.
+const CONFIG_FILE: &str = "input.json";
+
+/// How far an analog input has to be pushed before it counts as pressed.
+const PRESS_THRESHOLD: f32 = 0.5;
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        let mut input_map = config::load::<InputMap>(CONFIG_FILE).unwrap_or_default();
+        input_map.add_missing_actions();
+
+        app.insert_resource(input_map)
+            .init_resource::<ActionState>()
+            .init_resource::<Rebinding>()
+            .add_systems(
+                (capture_rebinding, update_action_state)
+                    .chain()
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_system(save_input_map.run_if(resource_changed::<InputMap>()));
+    }
+}
+
+/// Something the player can do, independent of what it's bound to.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Dash,
+    Pause,
+    /// Accepts whatever a menu or screen is asking.
+    Confirm,
+    /// Leaves the current menu.
+    Back,
+    /// Debug action that skips to the next level.
+    SkipLevel,
+}
+
+impl Action {
+    /// The actions players can rebind from the controls menu, debug actions
+    /// are left out.
+    pub const REBINDABLE: [Action; 9] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Jump,
+        Action::Dash,
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::Jump => "Jump",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::SkipLevel => "Skip Level",
+        }
+    }
+}
+
+/// A physical input an [`Action`] can be bound to.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// A gamepad axis pushed in one direction.
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(button) => write!(f, "Mouse {:?}", button),
+            Binding::GamepadButton(button) => write!(f, "{:?}", button),
+            Binding::GamepadAxis { axis, positive } => {
+                write!(f, "{:?}{}", axis, if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+/// Maps each [`Action`] to the inputs that trigger it.
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    /// Gamepad axis values closer to zero than this are ignored.
+    pub deadzone: f32,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::*;
+
+        let bindings = [
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::A),
+                    Key(KeyCode::Left),
+                    GamepadButton(GamepadButtonType::DPadLeft),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::D),
+                    Key(KeyCode::Right),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                Action::MoveUp,
+                vec![
+                    Key(KeyCode::W),
+                    Key(KeyCode::Up),
+                    GamepadButton(GamepadButtonType::DPadUp),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickY,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::S),
+                    Key(KeyCode::Down),
+                    GamepadButton(GamepadButtonType::DPadDown),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickY,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                Action::Jump,
+                vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::South)],
+            ),
+            (
+                Action::Dash,
+                vec![
+                    Key(KeyCode::LShift),
+                    Mouse(MouseButton::Right),
+                    GamepadButton(GamepadButtonType::West),
+                    GamepadButton(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Key(KeyCode::Escape),
+                    GamepadButton(GamepadButtonType::Start),
+                ],
+            ),
+            (
+                Action::Confirm,
+                vec![
+                    Key(KeyCode::Return),
+                    Key(KeyCode::NumpadEnter),
+                    GamepadButton(GamepadButtonType::South),
+                ],
+            ),
+            (
+                Action::Back,
+                vec![
+                    Key(KeyCode::Escape),
+                    Key(KeyCode::Back),
+                    GamepadButton(GamepadButtonType::East),
+                ],
+            ),
+            (Action::SkipLevel, vec![Key(KeyCode::T)]),
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+            deadzone: 0.2,
+        }
+    }
+}
+
+impl InputMap {
+    /// Replaces the binding at `slot` for `action`, or adds it if `slot` is
+    /// past the end of its current bindings.
+    pub fn rebind(&mut self, action: Action, slot: usize, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+
+        match bindings.get_mut(slot) {
+            Some(existing) => *existing = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    /// Gives actions added since the bindings were saved their default
+    /// bindings.
+    fn add_missing_actions(&mut self) {
+        for (action, bindings) in InputMap::default().bindings {
+            self.bindings.entry(action).or_insert(bindings);
+        }
+    }
+
+    /// Strips the deadzone from an axis value and rescales what's left back to
+    /// the `0.0..=1.0` range.
+    fn apply_deadzone(&self, value: f32) -> f32 {
+        if value <= self.deadzone {
+            0.0
+        } else {
+            ((value - self.deadzone) / (1.0 - self.deadzone)).min(1.0)
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct ActionData {
+    value: f32,
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+}
+
+/// The state of every [`Action`] this frame, read this instead of the raw
+/// input resources.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    actions: HashMap<Action, ActionData>,
+}
+
+impl ActionState {
+    fn data(&self, action: Action) -> ActionData {
+        self.actions.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.data(action).pressed
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.data(action).just_pressed
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.data(action).just_released
+    }
+
+    /// How strongly the action is held, in the range `0.0..=1.0`.
+    pub fn value(&self, action: Action) -> f32 {
+        self.data(action).value
+    }
+
+    /// Combines two opposing actions into a single axis in the range
+    /// `-1.0..=1.0`.
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+}
+
+/// Set to an action to bind it to the next input the player presses.
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub action: Option<Action>,
+    pub slot: usize,
+    /// The input that was just bound, ignored until it's released so it
+    /// doesn't also trigger what it was bound to.
+    held: Option<Binding>,
+}
+
+#[derive(SystemParam)]
+struct RawInputs<'w> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl RawInputs<'_> {
+    /// Reads a binding's value in the range `0.0..=1.0` from any connected
+    /// device.
+    fn value(&self, binding: &Binding, input_map: &InputMap) -> f32 {
+        let held = |pressed: bool| if pressed { 1.0 } else { 0.0 };
+
+        match *binding {
+            Binding::Key(key) => held(self.keyboard.pressed(key)),
+            Binding::Mouse(button) => held(self.mouse.pressed(button)),
+            Binding::GamepadButton(button_type) => held(self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(gamepad, button_type))
+            })),
+            Binding::GamepadAxis { axis, positive } => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                .map(|value| if positive { value } else { -value })
+                .map(|value| input_map.apply_deadzone(value))
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// The gamepad axes pushed far enough to count as pressed.
+    fn pushed_axes(&self, input_map: &InputMap) -> HashSet<GamepadAxis> {
+        self.gamepad_axes
+            .devices()
+            .filter(|axis| {
+                self.gamepad_axes
+                    .get(**axis)
+                    .is_some_and(|value| input_map.apply_deadzone(value.abs()) >= PRESS_THRESHOLD)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Finds an input that was pressed this frame, if any. Axes in
+    /// `pushed_before` were already pushed last frame, so they don't count.
+    fn just_pressed(
+        &self,
+        input_map: &InputMap,
+        pushed_before: &HashSet<GamepadAxis>,
+    ) -> Option<Binding> {
+        if let Some(key) = self.keyboard.get_just_pressed().next() {
+            return Some(Binding::Key(*key));
+        }
+
+        if let Some(button) = self.mouse.get_just_pressed().next() {
+            return Some(Binding::Mouse(*button));
+        }
+
+        if let Some(button) = self.gamepad_buttons.get_just_pressed().next() {
+            return Some(Binding::GamepadButton(button.button_type));
+        }
+
+        self.gamepad_axes
+            .devices()
+            .filter(|axis| !pushed_before.contains(*axis))
+            .find_map(|axis| {
+                let value = self.gamepad_axes.get(*axis)?;
+
+                (input_map.apply_deadzone(value.abs()) >= PRESS_THRESHOLD).then_some(
+                    Binding::GamepadAxis {
+                        axis: axis.axis_type,
+                        positive: value > 0.0,
+                    },
+                )
+            })
+    }
+}
+
+fn update_action_state(
+    inputs: RawInputs,
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    mut state: ResMut<ActionState>,
+) {
+    for (action, bindings) in input_map.bindings.iter() {
+        // Don't let the input that's being captured trigger actions.
+        let value = if rebinding.action.is_some() {
+            0.0
+        } else {
+            bindings
+                .iter()
+                .filter(|binding| rebinding.held != Some(**binding))
+                .map(|binding| inputs.value(binding, &input_map))
+                .fold(0.0, f32::max)
+        };
+
+        let data = state.actions.entry(*action).or_default();
+        let pressed = value >= PRESS_THRESHOLD;
+
+        data.just_pressed = pressed && !data.pressed;
+        data.just_released = !pressed && data.pressed;
+        data.pressed = pressed;
+        data.value = value;
+    }
+}
+
+fn capture_rebinding(
+    inputs: RawInputs,
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut pushed_axes: Local<HashSet<GamepadAxis>>,
+) {
+    // Axes have no just pressed state, so remember which were pushed last
+    // frame to tell a new push from a stick that's being held.
+    let pushed_before = std::mem::replace(&mut *pushed_axes, inputs.pushed_axes(&input_map));
+
+    if let Some(held) = rebinding.held {
+        if inputs.value(&held, &input_map) < PRESS_THRESHOLD {
+            rebinding.held = None;
+        }
+    }
+
+    let Some(action) = rebinding.action else {
+        return;
+    };
+
+    if let Some(binding) = inputs.just_pressed(&input_map, &pushed_before) {
+        info!("Binding {:?} to {:?}", action, binding);
+
+        input_map.rebind(action, rebinding.slot, binding);
+        rebinding.action = None;
+        rebinding.held = Some(binding);
+    }
+}
+
+fn save_input_map(input_map: Res<InputMap>) {
+    // Don't write out the defaults before anything has been rebound.
+    if !input_map.is_added() {
+        config::save(CONFIG_FILE, &*input_map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input_map(deadzone: f32) -> InputMap {
+        InputMap {
+            bindings: default(),
+            deadzone,
+        }
+    }
+
+    #[test]
+    fn ignores_values_inside_the_deadzone() {
+        let input_map = input_map(0.2);
+
+        assert_eq!(input_map.apply_deadzone(0.0), 0.0);
+        assert_eq!(input_map.apply_deadzone(0.1), 0.0);
+        assert_eq!(input_map.apply_deadzone(0.2), 0.0);
+        // Pushed the other way from the binding's direction.
+        assert_eq!(input_map.apply_deadzone(-0.8), 0.0);
+    }
+
+    #[test]
+    fn rescales_values_outside_the_deadzone() {
+        let input_map = input_map(0.2);
+
+        assert!((input_map.apply_deadzone(0.6) - 0.5).abs() < 1e-6);
+        assert_eq!(input_map.apply_deadzone(1.0), 1.0);
+        assert_eq!(input_map.apply_deadzone(1.5), 1.0);
+    }
+
+    #[test]
+    fn default_keys_trigger_one_action() {
+        let input_map = InputMap::default();
+
+        for (action, bindings) in &input_map.bindings {
+            for binding in bindings {
+                let Binding::Key(key) = binding else {
+                    continue;
+                };
+                // Pauses, or goes back when a menu is already open.
+                if *key == KeyCode::Escape {
+                    continue;
+                }
+
+                for (other, other_bindings) in &input_map.bindings {
+                    assert!(
+                        other == action || !other_bindings.contains(binding),
+                        "{:?} is bound to both {:?} and {:?}",
+                        key,
+                        action,
+                        other
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn passes_values_through_without_a_deadzone() {
+        let input_map = input_map(0.0);
+
+        assert_eq!(input_map.apply_deadzone(0.25), 0.25);
+        assert_eq!(input_map.apply_deadzone(1.0), 1.0);
+    }
+}
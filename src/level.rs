@@ -1,160 +1,387 @@
-use bevy::{prelude::*, utils::HashMap};
-use serde::Deserialize;
+use std::{fmt, time::Duration};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    sprite::collide_aabb,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{
-    loader::JsonFile,
+    audio,
+    checkpoint::{self, ActiveCheckpoint, LevelObject, LevelObjectState},
+    health::Hazard,
+    input::{Action, ActionState},
+    level_graph::LevelGraph,
+    loader::{AssetManifest, FailedAsset, FailedAssets, JsonFile, LevelAssets},
     physics::{Collider, Solid},
+    player::{Player, Velocity},
+    tiled::{TiledLayer, TiledMap, TiledObject, TiledTileset, Tileset},
+    transition::{self, TransitionKind},
     GameState,
 };
 
-/// A tiled map.
-#[derive(Deserialize)]
-pub struct TiledMap {
-    pub height: u32,
-    pub width: u32,
-
-    pub layers: Vec<TiledLayer>,
-
-    #[serde(rename = "nextlayerid")]
-    pub next_layer_id: u32,
-    #[serde(rename = "nextobjectid")]
-    pub next_object_id: u32,
+#[derive(Deserialize, Clone)]
+pub struct LevelData {
+    pub name: String,
+    pub map: String,
+    /// The track played while in this level, relative to the assets folder.
+    #[serde(default)]
+    pub music: Option<String>,
+    /// The ways out of this level, each matching an `exit` object in its map.
+    #[serde(default)]
+    pub exits: Vec<LevelExit>,
+    /// Touching any exit in this level completes the game, so it doesn't
+    /// list any exits of its own.
+    #[serde(default, rename = "final")]
+    pub is_final: bool,
+    /// Allows this level to lead back to a level before it, otherwise that's
+    /// reported as a mistake.
+    #[serde(default)]
+    pub loops: bool,
+}
 
-    #[serde(rename = "tileheight")]
-    pub tile_height: u32,
-    #[serde(rename = "tilewidth")]
-    pub tile_width: u32,
+impl LevelData {
+    /// The names of the levels this level leads to.
+    pub fn successors(&self) -> impl Iterator<Item = &str> {
+        self.exits.iter().map(|exit| exit.level.as_str())
+    }
 
-    pub tilesets: Vec<Tileset>,
+    pub fn exit(&self, name: &str) -> Option<&LevelExit> {
+        self.exits.iter().find(|exit| exit.name == name)
+    }
 }
 
-#[derive(Deserialize)]
-#[serde(untagged)]
-pub enum Tileset {
-    // Embedded,
-    External {
-        #[serde(rename = "firstgid")]
-        first_gid: u32,
-        source: String,
-    },
+#[derive(Deserialize, Clone)]
+pub struct LevelExit {
+    pub name: String,
+    /// The level this exit leads to.
+    pub level: String,
+    /// The spawn point to arrive at in `level`, its default spawn point if
+    /// not set.
+    #[serde(default)]
+    pub entrance: Option<String>,
+    #[serde(default)]
+    pub transition: TransitionKind,
 }
 
-/// A layer in a tile map.
-#[derive(Deserialize)]
-#[serde(untagged)]
-pub enum TiledLayer {
-    TileLayer(TiledTileLayer),
-    // ObjectLayer,
-}
+#[derive(Resource, Deserialize)]
+pub struct Levels(pub Vec<LevelData>);
 
-#[derive(Deserialize)]
-pub struct TiledTileLayer {
-    pub id: u32,
-    pub name: String,
+impl Levels {
+    pub fn get(&self, name: &str) -> Option<&LevelData> {
+        self.0.iter().find(|data| data.name == name)
+    }
 
-    pub data: Vec<u32>,
+    pub fn iter(&self) -> impl Iterator<Item = &LevelData> {
+        self.0.iter()
+    }
+}
 
-    pub height: u32,
-    pub width: u32,
+#[derive(Component)]
+pub struct Tile;
 
-    pub opacity: u32,
-    pub visible: bool,
+/// Takes the player to another level when they touch it.
+#[derive(Component)]
+pub struct Exit(pub String);
 
-    pub x: u32,
-    pub y: u32,
+#[derive(Resource)]
+pub struct CurrentLevel {
+    data: LevelData,
+    /// The spawn point the player enters the level at. `None` uses the
+    /// map's default spawn point.
+    entrance: Option<String>,
+    /// The exit the player is leaving through.
+    exit: Option<String>,
 }
 
-#[derive(Deserialize)]
-pub struct TiledTileset {
-    pub name: String,
-
-    pub columns: u32,
+impl CurrentLevel {
+    /// Enters `data` at `entrance`, or its default spawn point if `None`.
+    pub fn new(data: LevelData, entrance: Option<String>) -> Self {
+        Self {
+            data,
+            entrance,
+            exit: None,
+        }
+    }
 
-    pub image: String,
-    #[serde(rename = "imageheight")]
-    pub image_height: u32,
-    #[serde(rename = "imagewidth")]
-    pub image_width: u32,
+    pub fn data(&self) -> &LevelData {
+        &self.data
+    }
 
-    pub margin: u32,
-    pub spacing: u32,
+    pub fn name(&self) -> &str {
+        &self.data.name
+    }
 
-    #[serde(rename = "tilecount")]
-    pub tile_count: u32,
-    #[serde(rename = "tileheight")]
-    pub tile_height: u32,
-    #[serde(rename = "tilewidth")]
-    pub tile_width: u32,
+    pub fn entrance(&self) -> Option<&str> {
+        self.entrance.as_deref()
+    }
 
-    #[serde(default)]
-    pub tiles: Vec<TileProperties>,
+    /// The exit the player is leaving through, if it leads to another level.
+    pub fn taken_exit(&self) -> Option<&LevelExit> {
+        self.exit.as_ref().and_then(|exit| self.data.exit(exit))
+    }
 }
 
-#[derive(Deserialize, Clone)]
-pub struct TileProperties {
-    pub id: u32,
-    pub properties: Vec<Property>,
+/// Stats tracked for each level, keyed by level name.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct LevelStats {
+    pub deaths: HashMap<String, u32>,
+    /// How long has been spent playing each level.
+    pub time: HashMap<String, Duration>,
 }
 
-#[derive(Deserialize, Clone)]
-pub struct Property {
-    pub name: String,
-    pub value: Value,
-}
+impl LevelStats {
+    /// Whether a game has been started that can be continued.
+    pub fn in_progress(&self) -> bool {
+        !self.time.is_empty()
+    }
 
-struct PropertyMap(HashMap<u32, TileProperties>);
+    pub fn record_death(&mut self, level: &str) {
+        *self.deaths.entry(level.to_string()).or_default() += 1;
+    }
+}
 
-impl PropertyMap {
-    pub fn new(tileset: &TiledTileset) -> Self {
-        let mut map = HashMap::new();
+/// What the player has achieved in each level, keyed by level name. Unlike
+/// [`LevelStats`] this is kept when a new game is started.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct LevelRecords(pub HashMap<String, LevelRecord>);
 
-        for tile in tileset.tiles.iter() {
-            let id = tile.id;
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct LevelRecord {
+    /// Every collectible that's ever been picked up in the level.
+    pub collected: HashSet<u32>,
+    /// How many collectibles the level has, counted when it's played.
+    #[serde(default)]
+    pub collectibles: usize,
+    /// The quickest the level's been finished, timed from entering it to
+    /// leaving through an exit.
+    #[serde(default)]
+    pub best_time: Option<Duration>,
+}
 
-            map.insert(id, tile.clone());
-        }
+/// How long the current attempt at the level has taken. `None` if it can't
+/// set a best time, e.g. when continuing from a checkpoint in a loaded game.
+#[derive(Resource, Default)]
+struct RunTime(Option<Duration>);
 
-        Self(map)
+impl LevelRecords {
+    /// Whether the level has been reached at some point.
+    pub fn is_unlocked(&self, level: &str) -> bool {
+        self.0.contains_key(level)
     }
 }
 
-#[derive(Deserialize, Clone)]
-pub struct LevelData {
-    name: String,
-    map: String,
-    next: Option<String>,
+/// The area covered by the current map in world coordinates.
+#[derive(Resource, Default)]
+pub struct MapBounds(pub Rect);
+
+/// Where the player should be spawned in the current map, this is the
+/// position of their feet.
+#[derive(Resource, Default)]
+pub struct PlayerSpawn(pub Vec2);
+
+const LEVELS_PATH: &str = "levels.json";
+
+/// The object class used to mark spawn points in Tiled.
+const SPAWN_CLASS: &str = "spawn";
+/// The object class used for exits, named after an exit in `levels.json`.
+const EXIT_CLASS: &str = "exit";
+/// The name of the spawn point used when no entrance is requested.
+const DEFAULT_SPAWN: &str = "default";
+/// The exit taken when skipping a final level, which doesn't list its exits.
+const SKIPPED_EXIT: &str = "skip";
+
+#[derive(Debug)]
+pub enum LevelError {
+    MapNotLoaded(String),
+    TilesetNotLoaded {
+        map: String,
+        tileset: String,
+    },
+    MissingSpawnPoint {
+        map: String,
+        entrance: Option<String>,
+    },
 }
 
-#[derive(Resource, Deserialize)]
-pub struct Levels(Vec<LevelData>);
-
-impl Levels {
-    fn get(&self, name: &String) -> &LevelData {
-        self.0.iter().find(|data| &data.name == name).unwrap()
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::MapNotLoaded(map) => write!(f, "map `{}` isn't loaded", map),
+            LevelError::TilesetNotLoaded { map, tileset } => write!(
+                f,
+                "map `{}` uses tileset `{}` which isn't loaded",
+                map, tileset
+            ),
+            LevelError::MissingSpawnPoint {
+                map,
+                entrance: Some(entrance),
+            } => write!(f, "map `{}` has no spawn point named `{}`", map, entrance),
+            LevelError::MissingSpawnPoint {
+                map,
+                entrance: None,
+            } => write!(
+                f,
+                "map `{}` has no spawn points, add a `{}` object to one of its object layers",
+                map, SPAWN_CLASS
+            ),
+        }
     }
 }
 
-#[derive(Component)]
-pub struct Tile;
-
-#[derive(Resource)]
-pub struct CurrentLevel(LevelData);
+impl std::error::Error for LevelError {}
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(setup_levels.in_schedule(OnExit(GameState::Loading)))
+        app.init_resource::<PlayerSpawn>()
+            .init_resource::<MapBounds>()
+            .init_resource::<LevelStats>()
+            .init_resource::<LevelRecords>()
+            .init_resource::<RunTime>()
+            .add_system(
+                // The state has already changed when this runs, so leaving
+                // for the error screen can be detected.
+                setup_levels
+                    .run_if(not(in_state(GameState::LoadError)))
+                    .in_schedule(OnExit(GameState::Loading)),
+            )
             .add_system(spawn_map.in_schedule(OnEnter(GameState::Playing)))
+            .add_system(start_run.in_schedule(OnEnter(GameState::Playing)))
             .add_system(test.in_set(OnUpdate(GameState::Playing)))
-            .add_system(despawn_map.in_schedule(OnEnter(GameState::Transitioning)))
-            .add_system(level_transition.in_set(OnUpdate(GameState::Transitioning)));
+            .add_system(touch_exits.in_set(OnUpdate(GameState::Playing)))
+            .add_system(track_level_time.in_set(OnUpdate(GameState::Playing)))
+            .add_system(
+                record_progress
+                    .run_if(
+                        resource_changed::<CurrentLevel>()
+                            .or_else(resource_changed::<LevelObjectState>()),
+                    )
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(detect_map_changes.in_set(OnUpdate(GameState::Playing)))
+            .add_systems(
+                (despawn_map, spawn_map, keep_player_in_map, finish_reload)
+                    .chain()
+                    .after(detect_map_changes)
+                    .distributive_run_if(resource_exists::<ReloadMap>())
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(
+                stream_level_assets
+                    .run_if(resource_changed::<CurrentLevel>())
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(despawn_map.in_schedule(OnExit(GameState::Dying)))
+            .add_system(despawn_map.in_schedule(OnEnter(GameState::Menu)))
+            .add_system(cancel_run.in_schedule(OnEnter(GameState::Menu)))
+            .add_systems(
+                (despawn_map, level_transition)
+                    .chain()
+                    .distributive_run_if(transition::screen_covered)
+                    .in_set(OnUpdate(GameState::Transitioning)),
+            );
     }
 }
 
-fn test(keyboard: Res<Input<KeyCode>>, mut next_state: ResMut<NextState<GameState>>) {
-    if keyboard.just_released(KeyCode::T) {
+fn test(
+    actions: Res<ActionState>,
+    mut current: ResMut<CurrentLevel>,
+    mut run: ResMut<RunTime>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_released(Action::SkipLevel) {
+        // Skipping a level doesn't count as finishing it.
+        run.0 = None;
+
+        // Leaving without an exit restarts the level, so a final level still
+        // needs one to be completed.
+        let exit = current
+            .data
+            .exits
+            .first()
+            .map_or(SKIPPED_EXIT, |exit| exit.name.as_str());
+        current.exit = Some(exit.to_string());
+        next_state.set(GameState::Transitioning);
+    }
+}
+
+fn track_level_time(
+    time: Res<Time>,
+    current: Res<CurrentLevel>,
+    mut stats: ResMut<LevelStats>,
+    mut run: ResMut<RunTime>,
+) {
+    *stats.time.entry(current.name().to_string()).or_default() += time.delta();
+
+    if let Some(run) = &mut run.0 {
+        *run += time.delta();
+    }
+}
+
+/// Starts timing the level unless the player's respawning at a checkpoint,
+/// where the clock keeps going so dying can't be used as a shortcut.
+fn start_run(
+    current: Res<CurrentLevel>,
+    active_checkpoint: Res<ActiveCheckpoint>,
+    mut run: ResMut<RunTime>,
+) {
+    if active_checkpoint.for_level(current.name()).is_none() {
+        run.0 = Some(Duration::ZERO);
+    }
+}
+
+/// Stops timing once the game's left, as the attempt can't be finished
+/// without playing the level again.
+fn cancel_run(mut run: ResMut<RunTime>) {
+    run.0 = None;
+}
+
+/// Unlocks the level being played and keeps hold of what's been collected in
+/// it.
+fn record_progress(
+    current: Res<CurrentLevel>,
+    state: Res<LevelObjectState>,
+    asset_server: Res<AssetServer>,
+    maps: Res<Assets<TiledMap>>,
+    mut records: ResMut<LevelRecords>,
+) {
+    let record = records.0.entry(current.name().to_string()).or_default();
+    record.collected.extend(state.collected.iter().copied());
+
+    let handle: Handle<TiledMap> = asset_server.get_handle(&current.data.map);
+    if let Some(map) = maps.get(&handle) {
+        record.collectibles = map
+            .objects()
+            .filter(|object| object.class == checkpoint::COLLECTIBLE_CLASS)
+            .count();
+    }
+}
+
+fn touch_exits(
+    player: Query<(&Transform, &Collider), With<Player>>,
+    exits: Query<(&Exit, &Transform, &Collider)>,
+    mut current: ResMut<CurrentLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (player_transform, player_collider) = player.single();
+
+    let touched = exits.iter().find(|(_, transform, collider)| {
+        collide_aabb::collide(
+            player_transform.translation,
+            player_collider.size,
+            transform.translation,
+            collider.size,
+        )
+        .is_some()
+    });
+
+    if let Some((exit, _, _)) = touched {
+        current.exit = Some(exit.0.clone());
         next_state.set(GameState::Transitioning);
     }
 }
@@ -162,70 +389,114 @@ fn test(keyboard: Res<Input<KeyCode>>, mut next_state: ResMut<NextState<GameStat
 pub fn setup_levels(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    manifest: Res<AssetManifest>,
     mut json_files: ResMut<Assets<JsonFile>>,
+    mut failed_assets: ResMut<FailedAssets>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    let handle: Handle<JsonFile> = asset_server.get_handle("levels.json");
+    let handle: Handle<JsonFile> = asset_server.get_handle(LEVELS_PATH);
     let json = json_files
         .get_mut(&handle)
         .expect("Failed to get JSON file `levels.json`");
     // Should only have to create this once after loading so its okay to
     // take the file data.
-    let levels = serde_json::from_value::<Levels>(json.take()).unwrap();
-    let current = CurrentLevel(levels.0[0].clone());
+    let result = serde_json::from_value::<Levels>(json.take())
+        .map_err(|err| err.to_string())
+        .and_then(|levels| match LevelGraph::new(&levels, &manifest) {
+            Ok(graph) => Ok((levels, graph)),
+            Err(errors) => Err(errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")),
+        });
+
+    let (levels, graph) = match result {
+        Ok(levels) => levels,
+        Err(reason) => {
+            failed_assets.0.push(FailedAsset {
+                path: LEVELS_PATH.into(),
+                reason,
+            });
+            next_state.set(GameState::LoadError);
+            return;
+        }
+    };
+
+    let current = CurrentLevel {
+        data: levels.get(graph.start()).unwrap().clone(),
+        entrance: None,
+        exit: None,
+    };
     commands.insert_resource(levels);
+    commands.insert_resource(graph);
     commands.insert_resource(current);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_map(
     mut commands: Commands,
     current: Res<CurrentLevel>,
     asset_server: Res<AssetServer>,
-    mut json_data: ResMut<Assets<JsonFile>>,
-    mut atlases: ResMut<Assets<TextureAtlas>>,
+    maps: Res<Assets<TiledMap>>,
+    tilesets: Res<Assets<TiledTileset>>,
+    mut player_spawn: ResMut<PlayerSpawn>,
+    mut map_bounds: ResMut<MapBounds>,
+    object_state: Res<LevelObjectState>,
+    active_checkpoint: Res<ActiveCheckpoint>,
+    mut failed_assets: ResMut<FailedAssets>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
     info!("Spawning level");
 
-    let path = &current.0.map;
-    let handle: Handle<JsonFile> = asset_server.get_handle(path);
-    let json = json_data
-        .get_mut(&handle)
-        .expect("Failed to get JSON data.");
-
-    let tiled_map_data = serde_json::from_value::<TiledMap>(json.clone()).unwrap();
-
-    // store tileset data and their gids.
-    let mut tilesets = vec![];
+    let path = &current.data.map;
+    // Nothing's spawned if the map can't be, the error is shown instead.
+    let mut fail = |err: LevelError| {
+        failed_assets.0.push(FailedAsset {
+            path: path.into(),
+            reason: err.to_string(),
+        });
+        next_state.set(GameState::LoadError);
+    };
+
+    let handle: Handle<TiledMap> = asset_server.get_handle(path);
+    let Some(tiled_map_data) = maps.get(&handle) else {
+        fail(LevelError::MapNotLoaded(path.clone()));
+        return;
+    };
 
     for tileset in tiled_map_data.tilesets.iter() {
-        match tileset {
-            Tileset::External { first_gid, source } => {
-                // HACK
-                let source = &source[2..];
-
-                let handle: Handle<JsonFile> = asset_server.get_handle(source);
-                let json_data = json_data
-                    .get_mut(&handle)
-                    .expect("Failed to get JSON data.");
-
-                let tileset_data =
-                    serde_json::from_value::<TiledTileset>(json_data.clone()).unwrap();
-
-                let properties = PropertyMap::new(&tileset_data);
-
-                tilesets.push((*first_gid as u32, tileset_data, properties));
-            }
+        let Tileset::External { source, handle, .. } = tileset;
+        if tilesets.get(handle).is_none() {
+            fail(LevelError::TilesetNotLoaded {
+                map: path.clone(),
+                tileset: source.clone(),
+            });
+            return;
         }
     }
 
-    let mut gids = HashMap::new();
+    let checkpoint = active_checkpoint.for_level(&current.data.name);
 
-    for (i, (gid, tileset, _)) in tilesets.iter().enumerate() {
-        for j in 0..tileset.tile_count {
-            gids.insert((gid + j) as u32, i);
-        }
-    }
+    player_spawn.0 = match checkpoint {
+        Some(checkpoint) => checkpoint.position,
+        None => match find_spawn_point(tiled_map_data, path, current.entrance.as_deref()) {
+            Ok(position) => position,
+            Err(err) => {
+                fail(err);
+                return;
+            }
+        },
+    };
+
+    let width = (tiled_map_data.width * tiled_map_data.tile_width) as f32;
+    let height = (tiled_map_data.height * tiled_map_data.tile_height) as f32;
+    map_bounds.0 = Rect::from_corners(
+        tiled_map_data.to_world(0.0, height),
+        tiled_map_data.to_world(width, 0.0),
+    );
 
-    for (_i, layer) in tiled_map_data.layers.iter().enumerate() {
+    for layer in tiled_map_data.layers.iter() {
         match layer {
             TiledLayer::TileLayer(layer) => {
                 let width = layer.width;
@@ -248,35 +519,16 @@ pub fn spawn_map(
                         let y = tiled_map_data.height - 1 - y;
                         let y = (y * tiled_map_data.tile_height) as f32;
 
-                        let tileset_id = gids.get(&value).unwrap();
-
-                        let (gid, tileset, props) = &tilesets[*tileset_id];
-
-                        // HACK
-                        let source = &tileset.image[2..];
-
-                        let texture_handle = asset_server.get_handle(source);
-
-                        let rows = tileset.image_width / tileset.tile_width;
-
-                        let id = (value - gid) as f32;
-                        let atlas_col = tileset.columns as f32;
-
-                        let src_y = (id / atlas_col).floor() * tileset.tile_height as f32;
-                        let src_x = ((id % atlas_col) * tileset.tile_width as f32).ceil();
-
-                        let texture_atlas = TextureAtlas::from_grid(
-                            texture_handle,
-                            Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32),
-                            tileset.columns as usize,
-                            rows as usize,
-                            None,
-                            Some(Vec2::new(src_x, src_y)),
-                        );
-
-                        let texture_atlas_handle = atlases.add(texture_atlas);
+                        let Some((tileset, id)) = tiled_map_data.tileset_for(value) else {
+                            warn!("Tile {} in `{}` doesn't belong to any tileset", value, path);
+                            continue;
+                        };
+                        // Every tileset was checked before spawning anything.
+                        let Some(tileset) = tilesets.get(tileset) else {
+                            continue;
+                        };
 
-                        commands.spawn((
+                        let mut tile = commands.spawn((
                             Tile,
                             Solid,
                             Collider {
@@ -287,42 +539,294 @@ pub fn spawn_map(
                                 ),
                             },
                             SpriteSheetBundle {
-                                texture_atlas: texture_atlas_handle.clone(),
+                                sprite: TextureAtlasSprite::new(id as usize),
+                                texture_atlas: tileset.atlas.clone(),
                                 transform: Transform::from_xyz(x, y, 100.),
                                 ..default()
                             },
                         ));
 
-                        if let Some(props) = props.0.get(&(id as u32)) {
-                            for _prop in props.properties.iter() {}
+                        for prop in tileset.tile_properties(id) {
+                            match (prop.name.as_str(), &prop.value) {
+                                ("hazard", Value::Bool(false)) => {}
+                                // Hazards can either be flagged with a bool or
+                                // give the amount of damage they do.
+                                ("hazard", value) => {
+                                    let damage = value.as_u64().unwrap_or(1) as u32;
+                                    tile.insert(Hazard { damage });
+                                }
+                                _ => {}
+                            }
                         }
                     }
                 }
-            } // TiledLayer::ObjectLayer => todo!(),
+            }
+            TiledLayer::ObjectLayer(layer) => {
+                for object in layer.objects.iter() {
+                    if object.class == EXIT_CLASS {
+                        spawn_exit(&mut commands, tiled_map_data, object, &current.data);
+                        continue;
+                    }
+
+                    let spawned = checkpoint::spawn_object(
+                        &mut commands,
+                        tiled_map_data,
+                        object,
+                        &object_state,
+                        checkpoint,
+                    ) || audio::spawn_sound_object(
+                        &mut commands,
+                        &asset_server,
+                        tiled_map_data,
+                        object,
+                    );
+
+                    if !spawned && object.class != SPAWN_CLASS {
+                        warn!(
+                            "Unknown object class `{}` on object {} in `{}`",
+                            object.class, object.id, path
+                        );
+                    }
+                }
+            }
+            TiledLayer::Unsupported => {}
         }
     }
 }
 
-fn despawn_map(mut commands: Commands, tiles: Query<Entity, With<Tile>>) {
+fn spawn_exit(commands: &mut Commands, map: &TiledMap, object: &TiledObject, level: &LevelData) {
+    if !level.is_final && level.exit(&object.name).is_none() {
+        warn!(
+            "Exit `{}` ({}) isn't one of level `{}`'s exits",
+            object.name, object.id, level.name
+        );
+        return;
+    }
+
+    let center = map.to_world(
+        object.x + object.width / 2.0,
+        object.y + object.height / 2.0,
+    );
+
+    commands.spawn((
+        LevelObject(object.id),
+        Exit(object.name.clone()),
+        Collider {
+            size: Vec2::new(object.width, object.height),
+        },
+        TransformBundle::from_transform(Transform::from_translation(center.extend(0.0))),
+    ));
+}
+
+/// Finds the spawn point named `entrance` in `map`, or its default spawn
+/// point if `entrance` is `None`.
+fn find_spawn_point(
+    map: &TiledMap,
+    map_path: &str,
+    entrance: Option<&str>,
+) -> Result<Vec2, LevelError> {
+    let spawn_points = map
+        .objects()
+        .filter(|object| object.class == SPAWN_CLASS)
+        .collect::<Vec<_>>();
+
+    let spawn_point = match entrance {
+        Some(entrance) => spawn_points.iter().find(|object| object.name == entrance),
+        // Fall back to the first spawn point if there isn't one marked as the
+        // default.
+        None => spawn_points
+            .iter()
+            .find(|object| object.name == DEFAULT_SPAWN)
+            .or_else(|| spawn_points.first()),
+    };
+
+    spawn_point
+        .map(|object| map.to_world(object.x, object.y))
+        .ok_or_else(|| LevelError::MissingSpawnPoint {
+            map: map_path.to_string(),
+            entrance: entrance.map(str::to_string),
+        })
+}
+
+/// Marks that the current map changed on disk and should be respawned.
+#[derive(Resource)]
+struct ReloadMap;
+
+/// Watches for the current map or its tilesets being edited, which only
+/// happens when the asset server is watching for changes.
+fn detect_map_changes(
+    mut commands: Commands,
+    current: Res<CurrentLevel>,
+    asset_server: Res<AssetServer>,
+    maps: Res<Assets<TiledMap>>,
+    mut map_events: EventReader<AssetEvent<TiledMap>>,
+    mut tileset_events: EventReader<AssetEvent<TiledTileset>>,
+) {
+    let handle: Handle<TiledMap> = asset_server.get_handle(&current.data.map);
+
+    let map_changed = map_events.iter().any(
+        |event| matches!(event, AssetEvent::Modified { handle: modified } if *modified == handle),
+    );
+
+    let tileset_changed = tileset_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle: modified } => maps
+            .get(&handle)
+            .is_some_and(|map| map.uses_tileset(modified)),
+        _ => false,
+    });
+
+    if map_changed || tileset_changed {
+        info!("Reloading level '{}'", current.name());
+        commands.insert_resource(ReloadMap);
+    }
+}
+
+/// Leaves the player where they were when the map is reloaded, unless
+/// they're no longer inside it.
+fn keep_player_in_map(
+    bounds: Res<MapBounds>,
+    spawn: Res<PlayerSpawn>,
+    mut player: Query<(&mut Transform, &mut Velocity, &Collider), With<Player>>,
+) {
+    let (mut transform, mut velocity, collider) = player.single_mut();
+
+    if !bounds.0.contains(transform.translation.truncate()) {
+        let position = spawn.0 + Vec2::new(0.0, collider.size.y / 2.0);
+        transform.translation = position.extend(transform.translation.z);
+        *velocity = Velocity::default();
+    }
+}
+
+fn finish_reload(mut commands: Commands) {
+    commands.remove_resource::<ReloadMap>();
+}
+
+/// Everything that's spawned from a map.
+type MapEntityFilter = Or<(With<Tile>, With<LevelObject>)>;
+
+fn despawn_map(mut commands: Commands, tiles: Query<Entity, MapEntityFilter>) {
     for tile in tiles.iter() {
         commands.entity(tile).despawn();
     }
 }
 
+/// Loads the assets of the levels this one leads to in the background and
+/// releases the assets of the levels before it.
+fn stream_level_assets(
+    current: Res<CurrentLevel>,
+    manifest: Res<AssetManifest>,
+    asset_server: Res<AssetServer>,
+    mut level_assets: ResMut<LevelAssets>,
+) {
+    let mut keep = vec![current.name()];
+
+    for next in current.data.successors() {
+        level_assets.load(next, &manifest, &asset_server);
+        keep.push(next);
+    }
+
+    level_assets.retain(&keep);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn level_transition(
     mut current_level: ResMut<CurrentLevel>,
     levels: Res<Levels>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut object_state: ResMut<LevelObjectState>,
+    mut active_checkpoint: ResMut<ActiveCheckpoint>,
+    asset_server: Res<AssetServer>,
+    level_assets: Res<LevelAssets>,
+    run: Res<RunTime>,
+    mut records: ResMut<LevelRecords>,
 ) {
-    let level = &mut current_level.0;
+    // Left without going through an exit, the level is being restarted.
+    if current_level.exit.is_none() {
+        info!("Restarting level '{}'", current_level.name());
+        next_state.set(GameState::Playing);
+        return;
+    }
 
-    // if there's another level after this transition to it.
-    if let Some(next_id) = &level.next {
-        info!("Transitioning to next level: '{}'", next_id);
+    let name = current_level.name();
+    let record = records.0.entry(name.to_string()).or_default();
 
-        let next_level = levels.get(next_id);
-        current_level.0 = next_level.clone();
+    if let Some(time) = run.0 {
+        if record.best_time.is_none_or(|best| time < best) {
+            record.best_time = Some(time);
+        }
+    }
 
+    if current_level.data.is_final {
+        info!("Completed the game");
+        next_state.set(GameState::Complete);
+        return;
+    }
+
+    let exit = current_level
+        .taken_exit()
+        .expect("Only exits listed in `levels.json` are spawned");
+
+    info!(
+        "Transitioning to level '{}' through exit '{}'",
+        exit.level, exit.name
+    );
+
+    let next_level = levels
+        .get(&exit.level)
+        .expect("Levels are checked when they're loaded");
+    *current_level = CurrentLevel {
+        data: next_level.clone(),
+        entrance: exit.entrance.clone(),
+        exit: None,
+    };
+    *object_state = LevelObjectState::default();
+    active_checkpoint.0 = None;
+
+    // Only show the loading screen if the level didn't finish loading in
+    // the background.
+    if level_assets.is_loaded(current_level.name(), &asset_server) {
         next_state.set(GameState::Playing);
+    } else {
+        next_state.set(GameState::LoadingLevel);
+    }
+}
+
+/// The player's progress through the levels.
+#[derive(SystemParam)]
+pub struct LevelProgress<'w> {
+    current_level: ResMut<'w, CurrentLevel>,
+    levels: Res<'w, Levels>,
+    graph: Res<'w, LevelGraph>,
+    stats: ResMut<'w, LevelStats>,
+    object_state: ResMut<'w, LevelObjectState>,
+    active_checkpoint: ResMut<'w, ActiveCheckpoint>,
+}
+
+impl LevelProgress<'_> {
+    /// Forgets what's been done in the current level so it can be played
+    /// from the start again.
+    pub fn restart_level(&mut self) {
+        self.current_level.exit = None;
+        *self.object_state = LevelObjectState::default();
+        self.active_checkpoint.0 = None;
+    }
+
+    /// Starts the game over from the first level.
+    pub fn reset(&mut self) {
+        let start = self.graph.start().to_string();
+        self.start_at(&start);
+    }
+
+    /// Starts a new game from `level`, keeping the levels that have been
+    /// unlocked.
+    pub fn start_at(&mut self, level: &str) {
+        *self.current_level = CurrentLevel {
+            data: self.levels.get(level).unwrap().clone(),
+            entrance: None,
+            exit: None,
+        };
+        *self.stats = LevelStats::default();
+        *self.object_state = LevelObjectState::default();
+        self.active_checkpoint.0 = None;
     }
 }
@@ -0,0 +1,494 @@
+use std::{collections::VecDeque, fmt};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{level::Levels, loader::AssetManifest};
+
+/// A problem with `levels.json`.
+#[derive(Debug)]
+pub enum LevelGraphError {
+    NoLevels,
+    DuplicateName(String),
+    DuplicateExit {
+        level: String,
+        exit: String,
+    },
+    MissingMap {
+        level: String,
+        map: String,
+    },
+    UnknownLevel {
+        level: String,
+        next: String,
+    },
+    Unreachable(String),
+    /// A level that isn't final but has no exits.
+    DeadEnd(String),
+    /// A final level that lists exits, which would never be used.
+    FinalWithExits(String),
+    /// Levels that lead back to each other without any of them being marked
+    /// with `loops`.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for LevelGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelGraphError::NoLevels => write!(f, "there are no levels"),
+            LevelGraphError::DuplicateName(name) => {
+                write!(f, "there's more than one level named `{}`", name)
+            }
+            LevelGraphError::DuplicateExit { level, exit } => write!(
+                f,
+                "level `{}` has more than one exit named `{}`",
+                level, exit
+            ),
+            LevelGraphError::MissingMap { level, map } => write!(
+                f,
+                "level `{}` uses map `{}` which isn't in its asset manifest group",
+                level, map
+            ),
+            LevelGraphError::UnknownLevel { level, next } => {
+                write!(
+                    f,
+                    "level `{}` leads to `{}` which doesn't exist",
+                    level, next
+                )
+            }
+            LevelGraphError::Unreachable(level) => {
+                write!(f, "level `{}` can't be reached from the first level", level)
+            }
+            LevelGraphError::DeadEnd(level) => write!(
+                f,
+                "level `{}` has no exits, mark it as `final` if it's the last level",
+                level
+            ),
+            LevelGraphError::FinalWithExits(level) => {
+                write!(f, "level `{}` is final so it can't have exits", level)
+            }
+            LevelGraphError::Cycle(levels) => write!(
+                f,
+                "levels `{}` lead back to each other, mark one with `loops` if that's intended",
+                levels.join("` -> `")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelGraphError {}
+
+/// How the levels in `levels.json` lead to each other. The first level is
+/// where the game starts.
+#[derive(Resource, Debug)]
+pub struct LevelGraph {
+    start: String,
+    successors: HashMap<String, Vec<String>>,
+    predecessors: HashMap<String, Vec<String>>,
+}
+
+impl LevelGraph {
+    /// Builds the graph, reporting every problem with the levels at once.
+    pub fn new(levels: &Levels, manifest: &AssetManifest) -> Result<Self, Vec<LevelGraphError>> {
+        let mut errors = vec![];
+
+        let Some(start) = levels.iter().next() else {
+            return Err(vec![LevelGraphError::NoLevels]);
+        };
+
+        let mut successors = HashMap::<String, Vec<String>>::default();
+        let mut predecessors = HashMap::<String, Vec<String>>::default();
+
+        for level in levels.iter() {
+            if successors.contains_key(&level.name) {
+                errors.push(LevelGraphError::DuplicateName(level.name.clone()));
+                continue;
+            }
+
+            let has_map = manifest
+                .levels
+                .get(&level.name)
+                .is_some_and(|group| group.contains(&level.map));
+            if !has_map {
+                errors.push(LevelGraphError::MissingMap {
+                    level: level.name.clone(),
+                    map: level.map.clone(),
+                });
+            }
+
+            match (level.is_final, level.exits.is_empty()) {
+                (false, true) => errors.push(LevelGraphError::DeadEnd(level.name.clone())),
+                (true, false) => errors.push(LevelGraphError::FinalWithExits(level.name.clone())),
+                _ => {}
+            }
+
+            for (i, exit) in level.exits.iter().enumerate() {
+                if level.exits[..i].iter().any(|other| other.name == exit.name) {
+                    errors.push(LevelGraphError::DuplicateExit {
+                        level: level.name.clone(),
+                        exit: exit.name.clone(),
+                    });
+                }
+            }
+
+            // More than one exit can lead to the same level.
+            let mut nexts = Vec::<String>::new();
+            for next in level.successors() {
+                if !nexts.iter().any(|other| other == next) {
+                    nexts.push(next.to_string());
+                }
+            }
+            successors.insert(level.name.clone(), nexts);
+            predecessors.entry(level.name.clone()).or_default();
+        }
+
+        for (level, nexts) in successors.iter_mut() {
+            nexts.retain(|next| {
+                let exists = levels.iter().any(|level| &level.name == next);
+                if !exists {
+                    errors.push(LevelGraphError::UnknownLevel {
+                        level: level.clone(),
+                        next: next.clone(),
+                    });
+                }
+                exists
+            });
+
+            for next in nexts.iter() {
+                predecessors
+                    .entry(next.clone())
+                    .or_default()
+                    .push(level.clone());
+            }
+        }
+
+        let graph = Self {
+            start: start.name.clone(),
+            successors,
+            predecessors,
+        };
+
+        let reachable = graph.reachable();
+        errors.extend(
+            levels
+                .iter()
+                .filter(|level| !reachable.contains(level.name.as_str()))
+                .map(|level| LevelGraphError::Unreachable(level.name.clone())),
+        );
+        drop(reachable);
+
+        // Every cycle has to go through a level marked with `loops`, so any
+        // that are left once those are taken out are mistakes.
+        // Looked up by name to match the graph, which skips duplicate levels.
+        let unmarked = levels
+            .iter()
+            .map(|level| level.name.as_str())
+            .filter(|name| levels.get(name).is_some_and(|level| !level.loops))
+            .collect::<Vec<_>>();
+        errors.extend(
+            graph
+                .cycles_within(&unmarked)
+                .into_iter()
+                .map(LevelGraphError::Cycle),
+        );
+
+        if errors.is_empty() {
+            Ok(graph)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The level the game starts at.
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    /// The levels that `level` leads to.
+    pub fn successors(&self, level: &str) -> &[String] {
+        self.successors.get(level).map_or(&[], Vec::as_slice)
+    }
+
+    /// The levels that lead to `level`.
+    pub fn predecessors(&self, level: &str) -> &[String] {
+        self.predecessors.get(level).map_or(&[], Vec::as_slice)
+    }
+
+    fn reachable(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::default();
+        let mut queue = vec![self.start.as_str()];
+
+        while let Some(level) = queue.pop() {
+            if reachable.insert(level) {
+                queue.extend(self.successors(level).iter().map(String::as_str));
+            }
+        }
+
+        reachable
+    }
+
+    /// A cycle from each group of `levels` that lead back to each other,
+    /// only following exits between `levels`. Each is listed in the order
+    /// the levels lead to each other.
+    fn cycles_within<'a>(&'a self, levels: &[&'a str]) -> Vec<Vec<String>> {
+        let included = levels.iter().copied().collect::<HashSet<_>>();
+        let edges = levels
+            .iter()
+            .map(|&level| {
+                let nexts = self
+                    .successors(level)
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|next| included.contains(next))
+                    .collect();
+                (level, nexts)
+            })
+            .collect::<HashMap<_, Vec<_>>>();
+
+        let mut tarjan = Tarjan {
+            edges: &edges,
+            indices: default(),
+            low_links: default(),
+            stack: vec![],
+            on_stack: default(),
+            components: vec![],
+        };
+        for &level in levels {
+            if !tarjan.indices.contains_key(level) {
+                tarjan.visit(level);
+            }
+        }
+
+        tarjan
+            .components
+            .into_iter()
+            // A level on its own is only a cycle if it leads to itself.
+            .filter(|component| component.len() > 1 || edges[component[0]].contains(&component[0]))
+            .map(|component| {
+                let start = levels
+                    .iter()
+                    .find(|level| component.contains(level))
+                    .unwrap();
+
+                cycle_through(start, &component, &edges)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Tarjan's algorithm, which splits a graph into groups of nodes that can
+/// all reach each other.
+struct Tarjan<'a, 'e> {
+    edges: &'e HashMap<&'a str, Vec<&'a str>>,
+    /// The order nodes were visited in.
+    indices: HashMap<&'a str, usize>,
+    /// The earliest visited node on the stack that each node can reach.
+    low_links: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'a, '_> {
+    fn visit(&mut self, node: &'a str) {
+        let index = self.indices.len();
+        self.indices.insert(node, index);
+        self.low_links.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        let edges = self.edges;
+        for &next in edges.get(node).into_iter().flatten() {
+            let reached = if !self.indices.contains_key(next) {
+                self.visit(next);
+                self.low_links[next]
+            } else if self.on_stack.contains(next) {
+                self.indices[next]
+            } else {
+                continue;
+            };
+
+            let low_link = self.low_links[node].min(reached);
+            self.low_links.insert(node, low_link);
+        }
+
+        // Nothing visited before this node can be reached from it, so it and
+        // everything above it on the stack make up a component.
+        if self.low_links[node] == index {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+/// The shortest way from `start` back to itself through `component`, which
+/// must contain a cycle through `start`.
+fn cycle_through<'a>(
+    start: &'a str,
+    component: &[&'a str],
+    edges: &HashMap<&'a str, Vec<&'a str>>,
+) -> Vec<&'a str> {
+    let mut parents = HashMap::<&str, &str>::default();
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(node) = queue.pop_front() {
+        for &next in &edges[node] {
+            if next == start {
+                let mut cycle = vec![node];
+                while let Some(&parent) = parents.get(cycle.last().unwrap()) {
+                    cycle.push(parent);
+                }
+                cycle.reverse();
+                return cycle;
+            }
+
+            if component.contains(&next) && !parents.contains_key(next) {
+                parents.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    unreachable!("`{}` isn't part of a cycle", start)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::{LevelData, LevelExit};
+
+    use super::*;
+
+    /// A level with an exit named after each level it leads to, which is
+    /// final if there are none.
+    fn level(name: &str, nexts: &[&str]) -> LevelData {
+        LevelData {
+            name: name.to_string(),
+            map: format!("{}.tmj", name),
+            music: None,
+            exits: nexts
+                .iter()
+                .map(|next| LevelExit {
+                    name: format!("to_{}", next),
+                    level: next.to_string(),
+                    entrance: None,
+                    transition: default(),
+                })
+                .collect(),
+            is_final: nexts.is_empty(),
+            loops: false,
+        }
+    }
+
+    /// Builds the graph with every level's map in the manifest, except those
+    /// in `unloaded`.
+    fn build(levels: Vec<LevelData>, unloaded: &[&str]) -> Result<LevelGraph, Vec<String>> {
+        let manifest = AssetManifest {
+            common: vec![],
+            levels: levels
+                .iter()
+                .filter(|level| !unloaded.contains(&level.name.as_str()))
+                .map(|level| (level.name.clone(), vec![level.map.clone()]))
+                .collect(),
+        };
+
+        LevelGraph::new(&Levels(levels), &manifest).map_err(|errors| {
+            let mut errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            errors.sort();
+            errors
+        })
+    }
+
+    #[test]
+    fn builds_the_graph() {
+        let graph = build(
+            vec![
+                level("a", &["b", "c"]),
+                LevelData {
+                    loops: true,
+                    ..level("b", &["a", "c"])
+                },
+                level("c", &[]),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(graph.start(), "a");
+        assert_eq!(graph.successors("a"), ["b", "c"]);
+        assert_eq!(graph.predecessors("a"), ["b"]);
+        assert_eq!(graph.predecessors("c").len(), 2);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        assert_eq!(build(vec![], &[]).unwrap_err(), ["there are no levels"]);
+
+        let errors = build(
+            vec![
+                level("a", &["b", "b", "missing"]),
+                LevelData {
+                    loops: true,
+                    is_final: true,
+                    ..level("b", &["a"])
+                },
+                level("b", &[]),
+                LevelData {
+                    is_final: false,
+                    ..level("c", &[])
+                },
+            ],
+            &["c"],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                "level `a` has more than one exit named `to_b`",
+                "level `a` leads to `missing` which doesn't exist",
+                "level `b` is final so it can't have exits",
+                "level `c` can't be reached from the first level",
+                "level `c` has no exits, mark it as `final` if it's the last level",
+                "level `c` uses map `c.tmj` which isn't in its asset manifest group",
+                "there's more than one level named `b`",
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_loops_on_every_cycle() {
+        // `b` leads back to `a` through both `c` and `d`, only the way
+        // through `c` is marked.
+        let levels = vec![
+            level("a", &["b"]),
+            level("b", &["c", "d", "e"]),
+            LevelData {
+                loops: true,
+                ..level("c", &["b"])
+            },
+            level("d", &["b"]),
+            level("e", &["e", "f"]),
+            level("f", &[]),
+        ];
+
+        assert_eq!(
+            build(levels, &[]).unwrap_err(),
+            [
+                "levels `b` -> `d` lead back to each other, mark one with `loops` if that's intended",
+                "levels `e` lead back to each other, mark one with `loops` if that's intended",
+            ]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use crate::{
+    completion,
+    level::{LevelData, LevelProgress, LevelRecords, Levels},
+    level_graph::LevelGraph,
+    menu::{self, MenuAction, MenuEvent, MenuScreen, MenuStack},
+    GameState,
+};
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(handle_level_select.in_set(OnUpdate(GameState::Menu)));
+    }
+}
+
+#[derive(Component, Clone)]
+enum LevelSelectItem {
+    Level(String),
+    Back,
+}
+
+/// Describes the player's best in a level, e.g. `Forest: 1:05.3, 2/3
+/// collectibles`, or where to find it if it hasn't been reached.
+fn level_label(
+    level: &LevelData,
+    graph: &LevelGraph,
+    records: &LevelRecords,
+    unlocked: bool,
+) -> String {
+    if !unlocked {
+        return format!(
+            "{}: Locked, reached from {}",
+            level.name,
+            graph.predecessors(&level.name).join(" or ")
+        );
+    }
+
+    let Some(record) = records.0.get(&level.name) else {
+        return level.name.clone();
+    };
+
+    let mut label = match record.best_time {
+        Some(time) => format!("{}: {}", level.name, completion::format_time(time)),
+        None => format!("{}: Unfinished", level.name),
+    };
+    if record.collectibles > 0 {
+        label += &format!(
+            ", {}/{} collectibles",
+            record.collected.len(),
+            record.collectibles
+        );
+    }
+
+    label
+}
+
+pub fn spawn_level_select(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    levels: &Levels,
+    graph: &LevelGraph,
+    records: &LevelRecords,
+) {
+    menu::spawn_menu(commands, asset_server, "Level Select", |items| {
+        for level in levels.iter() {
+            // The first level is always open, even in a new save.
+            let unlocked = level.name == graph.start() || records.is_unlocked(&level.name);
+
+            items.add(
+                level_label(level, graph, records, unlocked),
+                unlocked,
+                LevelSelectItem::Level(level.name.clone()),
+            );
+        }
+
+        items.add("Back", true, LevelSelectItem::Back);
+    });
+}
+
+fn handle_level_select(
+    mut events: EventReader<MenuEvent>,
+    items: Query<&LevelSelectItem>,
+    mut stack: ResMut<MenuStack>,
+    mut progress: LevelProgress,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for action in menu::actions_in(&mut events, MenuScreen::LevelSelect) {
+        match action {
+            MenuAction::Back => {
+                stack.0.pop();
+            }
+            MenuAction::Confirm(item) => match items.get(item) {
+                Ok(LevelSelectItem::Level(level)) => {
+                    progress.start_at(level);
+                    next_state.set(GameState::LoadingLevel);
+                }
+                Ok(LevelSelectItem::Back) => {
+                    stack.0.pop();
+                }
+                Err(_) => {}
+            },
+            MenuAction::Adjust(..) => {}
+        }
+    }
+}
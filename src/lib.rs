@@ -1,13 +1,49 @@
+mod animation;
+mod audio;
+mod camera;
+mod checkpoint;
+mod completion;
+mod config;
+mod controls;
+mod health;
+mod input;
 mod physics;
 mod player;
+mod settings;
+mod tiled;
+mod transition;
 mod level;
+mod level_graph;
+mod level_select;
 mod loader;
+mod loading_screen;
+mod menu;
+mod pause;
+mod pixel_perfect;
+mod save;
 
+use animation::AnimationPlugin;
+use audio::InternalAudioPlugin;
 use bevy::prelude::*;
+use camera::CameraPlugin;
+use checkpoint::CheckpointPlugin;
+use completion::CompletionPlugin;
+use controls::ControlsPlugin;
+use health::HealthPlugin;
+use input::InputPlugin;
 use physics::PhysicsPlugin;
 use player::PlayerPlugin;
+use settings::SettingsPlugin;
 use level::LevelPlugin;
+use level_select::LevelSelectPlugin;
 use loader::LoadingPlugin;
+use loading_screen::LoadingScreenPlugin;
+use menu::MenuPlugin;
+use pause::PausePlugin;
+use pixel_perfect::PixelPerfectPlugin;
+use save::SavePlugin;
+use tiled::TiledPlugin;
+use transition::TransitionPlugin;
 
 #[cfg(debug_assertions)]
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
@@ -17,9 +53,20 @@ enum GameState {
     // During the loading State the LoadingPlugin will load our assets
     #[default]
     Loading,
+    // The title screen and its menus
+    Menu,
+    // Waiting for the current level's assets before playing it
+    LoadingLevel,
     // During this State the actual game logic is executed
     Playing,
     Transitioning,
+    // The player died and the death sequence is playing, the level is
+    // respawned when it finishes.
+    Dying,
+    // The final level was finished and the completion screen is showing
+    Complete,
+    // Assets the game needs failed to load, the game can't go any further
+    LoadError,
 }
 
 pub struct GamePlugin;
@@ -28,12 +75,27 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<GameState>();
 
+        app.add_plugin(InputPlugin);
         app.add_plugin(PlayerPlugin);
         app.add_plugin(PhysicsPlugin);
+        app.add_plugin(TiledPlugin);
         app.add_plugin(LoadingPlugin);
+        app.add_plugin(LoadingScreenPlugin);
         app.add_plugin(LevelPlugin);
-
-        app.add_system(setup.in_schedule(OnEnter(GameState::Loading)));
+        app.add_plugin(AnimationPlugin);
+        app.add_plugin(HealthPlugin);
+        app.add_plugin(CheckpointPlugin);
+        app.add_plugin(CompletionPlugin);
+        app.add_plugin(TransitionPlugin);
+        app.add_plugin(SettingsPlugin);
+        app.add_plugin(MenuPlugin);
+        app.add_plugin(PausePlugin);
+        app.add_plugin(SavePlugin);
+        app.add_plugin(LevelSelectPlugin);
+        app.add_plugin(InternalAudioPlugin);
+        app.add_plugin(CameraPlugin);
+        app.add_plugin(PixelPerfectPlugin);
+        app.add_plugin(ControlsPlugin);
 
         #[cfg(debug_assertions)]
         {
@@ -42,7 +104,3 @@ impl Plugin for GamePlugin {
         }
     }
 }
-
-fn setup(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
-}
@@ -1,20 +1,112 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
 use bevy::{
-    asset::{AssetLoader, LoadedAsset},
+    asset::{AssetLoader, HandleId, LoadState, LoadedAsset},
+    ecs::system::SystemParam,
     prelude::*,
     reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::{HashMap, HashSet},
 };
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::GameState;
+use crate::{level::CurrentLevel, GameState};
+
+/// The shortest time the loading screen is shown for, so it doesn't just
+/// flash up when everything loads quickly.
+const MIN_LOADING_SECONDS: f32 = 1.0;
 
 /// Track loading state and prevent unused loaded handles
 /// from being dropped.
 #[derive(Resource)]
 pub struct AssetsLoading(Vec<HandleUntyped>);
 
+/// Handles to the assets of the levels that are loaded or loading, keyed by
+/// level name. Removing a level lets Bevy unload its assets.
+#[derive(Resource, Default)]
+pub struct LevelAssets(HashMap<String, Vec<HandleUntyped>>);
+
+impl LevelAssets {
+    /// Starts loading the assets for `level` if they aren't already.
+    pub fn load(&mut self, level: &str, manifest: &AssetManifest, asset_server: &AssetServer) {
+        if self.0.contains_key(level) {
+            return;
+        }
+
+        info!("Loading assets for level '{}'", level);
+
+        let handles = manifest
+            .levels
+            .get(level)
+            .unwrap_or_else(|| panic!("No asset group for level `{}`", level))
+            .iter()
+            .map(|path| asset_server.load_untyped(path.as_str()))
+            .collect();
+
+        self.0.insert(level.to_string(), handles);
+    }
+
+    /// Releases the assets of every level other than `levels`.
+    pub fn retain(&mut self, levels: &[&str]) {
+        self.0.retain(|level, _| levels.contains(&level.as_str()));
+    }
+
+    pub fn is_loaded(&self, level: &str, asset_server: &AssetServer) -> bool {
+        self.0.get(level).is_some_and(|handles| {
+            asset_server.get_group_load_state(handles.iter().map(|handle| handle.id()))
+                == LoadState::Loaded
+        })
+    }
+}
+
+/// The assets the game loads, listed in `assets/manifest.json` since the web
+/// build can't list folders. It's checked against the assets by `build.rs`.
+///
+/// Only the files themselves are listed, the tilesets and images a map uses
+/// are loaded along with it.
+#[derive(Resource, Deserialize)]
+pub struct AssetManifest {
+    /// Assets every level needs.
+    pub common: Vec<String>,
+    /// The assets for each level, keyed by level name.
+    pub levels: HashMap<String, Vec<String>>,
+}
+
+impl Default for AssetManifest {
+    fn default() -> Self {
+        serde_json::from_str(include_str!("../assets/manifest.json"))
+            .expect("Invalid asset manifest")
+    }
+}
+
+/// How far through [`AssetsLoading`] we are, shown on the loading screen.
+#[derive(Resource, Default)]
+pub struct LoadingProgress {
+    /// Assets that have loaded, or failed and been replaced by a placeholder.
+    pub loaded: usize,
+    pub total: usize,
+    /// The first asset still being loaded.
+    pub current: Option<PathBuf>,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+#[derive(Resource)]
+struct LoadingTimer(Timer);
+
 #[derive(Debug, Deserialize, TypeUuid)]
 #[uuid = "39cadc56-aa9c-dead-ed40-a018b74b5052"]
 pub struct JsonFile(Value);
@@ -33,8 +125,56 @@ impl DerefMut for JsonFile {
     }
 }
 
-#[derive(Default)]
-struct JsonFileLoader;
+/// Why assets failed to load, filled in by our asset loaders since Bevy only
+/// logs the reason.
+#[derive(Resource, Clone, Default)]
+pub struct LoadErrors(Arc<Mutex<HashMap<PathBuf, String>>>);
+
+impl LoadErrors {
+    /// Remembers the error if `result` failed, then passes it on.
+    pub fn track(
+        &self,
+        path: &Path,
+        result: Result<(), bevy::asset::Error>,
+    ) -> Result<(), bevy::asset::Error> {
+        if let Err(err) = &result {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), err.to_string());
+        }
+
+        result
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.0.lock().unwrap().get(path).cloned()
+    }
+}
+
+/// An asset that failed to load.
+pub struct FailedAsset {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// The critical assets that failed to load, shown in [`GameState::LoadError`].
+#[derive(Resource, Default)]
+pub struct FailedAssets(pub Vec<FailedAsset>);
+
+struct JsonFileLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for JsonFileLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world
+                .get_resource_or_insert_with(LoadErrors::default)
+                .clone(),
+        }
+    }
+}
 
 impl AssetLoader for JsonFileLoader {
     fn load<'a>(
@@ -43,10 +183,11 @@ impl AssetLoader for JsonFileLoader {
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
         Box::pin(async move {
-            let content = serde_json::from_slice::<Value>(bytes)?;
-            let json = JsonFile(content);
-            load_context.set_default_asset(LoadedAsset::new(json));
-            Ok(())
+            let result = serde_json::from_slice::<Value>(bytes)
+                .map(|content| load_context.set_default_asset(LoadedAsset::new(JsonFile(content))))
+                .map_err(Into::into);
+
+            self.errors.track(load_context.path(), result)
         })
     }
 
@@ -62,47 +203,220 @@ impl Plugin for LoadingPlugin {
         app.add_asset::<JsonFile>()
             .init_asset_loader::<JsonFileLoader>()
             .add_system(setup.in_schedule(OnEnter(GameState::Loading)))
-            .add_system(update.in_set(OnUpdate(GameState::Loading)));
+            .init_resource::<FailedAssets>()
+            .init_resource::<LoadingProgress>()
+            .init_resource::<LevelAssets>()
+            .add_system(update.in_set(OnUpdate(GameState::Loading)))
+            .add_system(
+                // There's no level if `levels.json` was invalid.
+                update_level
+                    .run_if(resource_exists::<CurrentLevel>())
+                    .in_set(OnUpdate(GameState::LoadingLevel)),
+            )
+            .add_system(report_load_errors.in_schedule(OnEnter(GameState::LoadError)));
+
+        #[cfg(not(debug_assertions))]
+        app.add_system(show_load_errors.in_schedule(OnEnter(GameState::LoadError)));
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let mut assets = vec![];
+/// Whether the game can't run without the asset. Data files are critical
+/// while images can be replaced by a placeholder and sounds left silent.
+fn is_critical(path: &Path) -> bool {
+    !matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("png" | "ogg" | "wav")
+    )
+}
 
-    let paths = ["levels", "textures", "tilesets"];
+/// A magenta square that stands in for images that failed to load.
+fn placeholder_image() -> Image {
+    Image::new_fill(
+        Extent3d {
+            width: 16,
+            height: 16,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[255, 0, 255, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
 
-    for path in paths {
-        assets.extend(
-            asset_server
-                .load_folder(path)
-                .expect("Failed to load folder `levels`"),
-        );
-    }
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let manifest = AssetManifest::default();
 
-    assets.push(asset_server.load_untyped("levels.json"));
+    let assets = manifest
+        .common
+        .iter()
+        .map(|path| asset_server.load_untyped(path.as_str()))
+        .collect();
 
     commands.insert_resource(AssetsLoading(assets));
+    commands.insert_resource(manifest);
+    commands.insert_resource(LoadingTimer(Timer::from_seconds(
+        MIN_LOADING_SECONDS,
+        TimerMode::Once,
+    )));
+}
+
+/// Checks on assets that are loading, recording failures and progress.
+#[derive(SystemParam)]
+struct LoadTracker<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    errors: Res<'w, LoadErrors>,
+    images: ResMut<'w, Assets<Image>>,
+    failed_assets: ResMut<'w, FailedAssets>,
+    progress: ResMut<'w, LoadingProgress>,
+    replaced: Local<'s, HashSet<HandleId>>,
+}
+
+impl LoadTracker<'_, '_> {
+    /// Updates the progress of `handles`, returning whether they've all
+    /// loaded.
+    fn update(&mut self, handles: &[HandleUntyped]) -> bool {
+        let mut ready = true;
+
+        self.progress.loaded = 0;
+        self.progress.total = handles.len();
+        self.progress.current = None;
+
+        for handle in handles {
+            let path = self
+                .asset_server
+                .get_handle_path(handle.id())
+                .map(|path| path.path().to_path_buf())
+                .unwrap_or_default();
+
+            match self.asset_server.get_load_state(handle.id()) {
+                LoadState::Loaded => self.progress.loaded += 1,
+                LoadState::Failed => {
+                    self.progress.loaded += 1;
+
+                    let reason = self
+                        .errors
+                        .get(&path)
+                        .unwrap_or_else(|| "the file is missing or invalid".to_string());
+
+                    if is_critical(&path) {
+                        self.failed_assets.0.push(FailedAsset { path, reason });
+                    } else if self.replaced.insert(handle.id()) {
+                        warn!("Using a placeholder for '{}': {}", path.display(), reason);
+
+                        if path.extension().is_some_and(|ext| ext == "png") {
+                            self.images.set_untracked(handle.id(), placeholder_image());
+                        }
+                    }
+                }
+                _ => {
+                    // NotLoaded/Loading: not fully ready yet
+                    ready = false;
+                    self.progress.current.get_or_insert(path);
+                }
+            }
+        }
+
+        ready
+    }
+
+    fn failed(&self) -> bool {
+        !self.failed_assets.0.is_empty()
+    }
 }
 
 fn update(
-    // mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    time: Res<Time>,
     loading: Res<AssetsLoading>,
+    mut timer: ResMut<LoadingTimer>,
+    mut tracker: LoadTracker,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
-    use bevy::asset::LoadState;
+    timer.0.tick(time.delta());
 
-    match asset_server.get_group_load_state(loading.0.iter().map(|h| h.id())) {
-        LoadState::Failed => {
-            // one of our assets had an error
-        }
-        LoadState::Loaded => {
-            // all assets are now ready
-            info!("Starting game");
-            next_state.set(GameState::Playing);
-        }
-        _ => {
-            // NotLoaded/Loading: not fully ready yet
-        }
+    let ready = tracker.update(&loading.0);
+
+    if tracker.failed() {
+        next_state.set(GameState::LoadError);
+    } else if ready && timer.0.finished() {
+        // all assets are now ready
+        next_state.set(GameState::Menu);
+    }
+}
+
+/// Waits for the current level's assets, which are usually loaded in the
+/// background while the previous level is played.
+fn update_level(
+    asset_server: Res<AssetServer>,
+    manifest: Res<AssetManifest>,
+    current: Res<CurrentLevel>,
+    mut level_assets: ResMut<LevelAssets>,
+    mut tracker: LoadTracker,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    level_assets.load(current.name(), &manifest, &asset_server);
+
+    let ready = tracker.update(&level_assets.0[current.name()]);
+
+    if tracker.failed() {
+        next_state.set(GameState::LoadError);
+    } else if ready {
+        info!("Starting level");
+        next_state.set(GameState::Playing);
     }
 }
+
+fn report_load_errors(failed_assets: Res<FailedAssets>) {
+    for failed in failed_assets.0.iter() {
+        error!(
+            "Failed to load '{}': {}",
+            failed.path.display(),
+            failed.reason
+        );
+    }
+}
+
+/// Release builds have no console to log to so the errors are also shown on
+/// screen.
+#[cfg(not(debug_assertions))]
+fn show_load_errors(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    failed_assets: Res<FailedAssets>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let style = TextStyle {
+        font,
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::all(Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                gap: Size::height(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "The game's files couldn't be loaded",
+                TextStyle {
+                    font_size: 32.0,
+                    ..style.clone()
+                },
+            ));
+
+            for failed in failed_assets.0.iter() {
+                parent.spawn(TextBundle::from_section(
+                    format!("{}: {}", failed.path.display(), failed.reason),
+                    style.clone(),
+                ));
+            }
+        });
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+
+use crate::{loader::LoadingProgress, GameState};
+
+/// How long the loading screen takes to fade out once the game starts.
+const FADE_SECONDS: f32 = 0.5;
+const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const BAR_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BAR_FILL_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+
+pub struct LoadingScreenPlugin;
+
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_loading_screen.in_schedule(OnEnter(GameState::Loading)))
+            .add_system(spawn_loading_screen.in_schedule(OnEnter(GameState::LoadingLevel)))
+            .add_system(
+                update_loading_screen
+                    .run_if(in_state(GameState::Loading).or_else(in_state(GameState::LoadingLevel)))
+                    .run_if(resource_changed::<LoadingProgress>()),
+            )
+            .add_system(start_fade.in_schedule(OnEnter(GameState::Menu)))
+            .add_system(start_fade.in_schedule(OnEnter(GameState::Playing)))
+            .add_system(despawn_loading_screen.in_schedule(OnEnter(GameState::LoadError)))
+            .add_system(fade_loading_screen);
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct ProgressBarFill;
+
+#[derive(Component)]
+struct ProgressText;
+
+#[derive(Component)]
+struct CurrentFileText;
+
+/// Fades the loading screen out, then removes it.
+#[derive(Component)]
+struct Fade(Timer);
+
+fn spawn_loading_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    screens: Query<(), With<LoadingScreen>>,
+) {
+    // The screen from the initial load stays up while the first level loads.
+    if !screens.is_empty() {
+        return;
+    }
+
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            LoadingScreen,
+            NodeBundle {
+                style: Style {
+                    size: Size::all(Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::height(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                // Draw over the level while fading out.
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Loading",
+                TextStyle {
+                    font_size: 40.0,
+                    ..style.clone()
+                },
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(400.0), Val::Px(16.0)),
+                        ..default()
+                    },
+                    background_color: BAR_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        ProgressBarFill,
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            background_color: BAR_FILL_COLOR.into(),
+                            ..default()
+                        },
+                    ));
+                });
+
+            parent.spawn((ProgressText, TextBundle::from_section("", style.clone())));
+            parent.spawn((
+                CurrentFileText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::GRAY,
+                        ..style
+                    },
+                ),
+            ));
+        });
+}
+
+fn update_loading_screen(
+    progress: Res<LoadingProgress>,
+    mut fill: Query<&mut Style, With<ProgressBarFill>>,
+    mut progress_text: Query<&mut Text, (With<ProgressText>, Without<CurrentFileText>)>,
+    mut current_text: Query<&mut Text, (With<CurrentFileText>, Without<ProgressText>)>,
+) {
+    for mut style in &mut fill {
+        style.size.width = Val::Percent(progress.fraction() * 100.0);
+    }
+
+    for mut text in &mut progress_text {
+        text.sections[0].value = format!("{}/{}", progress.loaded, progress.total);
+    }
+
+    for mut text in &mut current_text {
+        text.sections[0].value = progress
+            .current
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+    }
+}
+
+fn start_fade(
+    mut commands: Commands,
+    screens: Query<Entity, (With<LoadingScreen>, Without<Fade>)>,
+) {
+    for entity in &screens {
+        commands
+            .entity(entity)
+            .insert(Fade(Timer::from_seconds(FADE_SECONDS, TimerMode::Once)));
+    }
+}
+
+fn fade_loading_screen(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut screens: Query<(Entity, &mut Fade), With<LoadingScreen>>,
+    children: Query<&Children>,
+    mut backgrounds: Query<&mut BackgroundColor>,
+    mut texts: Query<&mut Text>,
+) {
+    for (entity, mut fade) in &mut screens {
+        fade.0.tick(time.delta());
+
+        if fade.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let alpha = 1.0 - fade.0.percent();
+
+        for node in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+            // Leave transparent nodes, like text, alone.
+            if let Ok(mut background) = backgrounds.get_mut(node) {
+                if background.0.a() > 0.0 {
+                    background.0.set_a(alpha);
+                }
+            }
+
+            if let Ok(mut text) = texts.get_mut(node) {
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_a(alpha);
+                }
+            }
+        }
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+}
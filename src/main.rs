@@ -13,15 +13,26 @@ fn main() {
     App::new()
         .insert_resource(Msaa::Off)
         .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Bevy game".to_string(), // ToDo
-                resolution: (800., 600.).into(),
-                canvas: Some("#bevy".to_owned()),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "Bevy game".to_string(), // ToDo
+                        resolution: (800., 600.).into(),
+                        canvas: Some("#bevy".to_owned()),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // Keep the pixel art crisp rather than blurring it when it's
+                // scaled up
+                .set(ImagePlugin::default_nearest())
+                .set(AssetPlugin {
+                    // Reload maps edited in Tiled while the game is running
+                    watch_for_changes: cfg!(all(debug_assertions, not(target_arch = "wasm32"))),
+                    ..default()
+                }),
+        )
         .add_plugin(GamePlugin)
         .add_system(set_window_icon.on_startup())
         .run();
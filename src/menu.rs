@@ -0,0 +1,471 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    controls,
+    input::{Action, ActionState, InputMap, Rebinding},
+    level::{LevelProgress, LevelRecords, LevelStats, Levels},
+    level_graph::LevelGraph,
+    level_select, pause, save,
+    settings::Settings,
+    GameState,
+};
+
+const TITLE: &str = "Bevy game";
+const ITEM_COLOR: Color = Color::GRAY;
+const SELECTED_ITEM_COLOR: Color = Color::WHITE;
+const DISABLED_ITEM_COLOR: Color = Color::DARK_GRAY;
+/// How much the volume settings change with each press.
+const VOLUME_STEP: f32 = 0.1;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuStack>()
+            .add_event::<MenuEvent>()
+            .add_system(open_title.in_schedule(OnEnter(GameState::Menu)))
+            .add_system(close_menus.in_schedule(OnExit(GameState::Menu)))
+            .add_systems((navigate_menu, highlight_menu_items).chain())
+            .add_system(show_menu.run_if(resource_changed::<MenuStack>()))
+            .add_system(handle_title.in_set(OnUpdate(GameState::Menu)))
+            .add_system(handle_settings)
+            .add_system(update_settings_labels.run_if(resource_changed::<Settings>()));
+    }
+}
+
+/// A screen of the menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuScreen {
+    Title,
+    Settings,
+    Controls,
+    Pause,
+    Slots,
+    LevelSelect,
+}
+
+/// The menus that are open, only the last one is shown. Going back closes it
+/// and shows the one before it.
+#[derive(Resource, Default)]
+pub struct MenuStack(pub Vec<MenuScreen>);
+
+impl MenuStack {
+    pub fn top(&self) -> Option<MenuScreen> {
+        self.0.last().copied()
+    }
+}
+
+/// A list of items that can be navigated with the keyboard, a gamepad or the
+/// mouse.
+#[derive(Component)]
+pub struct Menu {
+    items: Vec<Entity>,
+    selected: usize,
+}
+
+#[derive(Component)]
+pub struct MenuItem {
+    pub enabled: bool,
+}
+
+/// Sent when the player uses the current menu.
+pub struct MenuEvent {
+    /// The screen that was showing. Screens only handle their own events, so
+    /// going back from one doesn't also go back from the one it returns to.
+    pub screen: MenuScreen,
+    pub action: MenuAction,
+}
+
+#[derive(Clone, Copy)]
+pub enum MenuAction {
+    Confirm(Entity),
+    /// An item was pushed left or right, for changing values.
+    Adjust(Entity, f32),
+    Back,
+}
+
+/// The actions the player took in `screen`, skipping those meant for other
+/// screens.
+pub fn actions_in<'a>(
+    events: &'a mut EventReader<'_, '_, MenuEvent>,
+    screen: MenuScreen,
+) -> impl Iterator<Item = MenuAction> + 'a {
+    events
+        .iter()
+        .filter(move |event| event.screen == screen)
+        .map(|event| event.action)
+}
+
+/// Adds items to a menu being spawned by [`spawn_menu`].
+pub struct MenuItems<'p, 'w, 's, 'a> {
+    parent: &'p mut ChildBuilder<'w, 's, 'a>,
+    style: TextStyle,
+    items: Vec<(Entity, bool)>,
+}
+
+impl MenuItems<'_, '_, '_, '_> {
+    /// Adds an item, `bundle` should hold a component saying what it does.
+    pub fn add(&mut self, label: impl Into<String>, enabled: bool, bundle: impl Bundle) {
+        let item = self
+            .parent
+            .spawn((
+                TextBundle::from_section(label, self.style.clone()),
+                Interaction::default(),
+                MenuItem { enabled },
+                bundle,
+            ))
+            .id();
+
+        self.items.push((item, enabled));
+    }
+}
+
+/// Marks the root entity of the menu being shown.
+#[derive(Component)]
+struct MenuRoot;
+
+/// Spawns a menu with a title and the items added by `build_items`.
+pub fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    title: &str,
+    build_items: impl FnOnce(&mut MenuItems),
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let style = TextStyle {
+        font,
+        font_size: 28.0,
+        color: ITEM_COLOR,
+    };
+
+    let mut items = vec![];
+
+    let mut root = commands.spawn((
+        MenuRoot,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::all(Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                gap: Size::height(Val::Px(12.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        },
+    ));
+
+    root.with_children(|parent| {
+        parent.spawn(
+            TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 48.0,
+                    color: SELECTED_ITEM_COLOR,
+                    ..style.clone()
+                },
+            )
+            .with_style(Style {
+                margin: UiRect::bottom(Val::Px(24.0)),
+                ..default()
+            }),
+        );
+
+        let mut menu_items = MenuItems {
+            parent,
+            style,
+            items: vec![],
+        };
+        build_items(&mut menu_items);
+        items = menu_items.items;
+    });
+
+    let selected = items.iter().position(|(_, enabled)| *enabled).unwrap_or(0);
+
+    root.insert(Menu {
+        items: items.into_iter().map(|(item, _)| item).collect(),
+        selected,
+    });
+}
+
+fn navigate_menu(
+    actions: Res<ActionState>,
+    stack: Res<MenuStack>,
+    mut menus: Query<&mut Menu>,
+    items: Query<(&MenuItem, &Interaction), Changed<Interaction>>,
+    enabled: Query<&MenuItem>,
+    mut events: EventWriter<MenuEvent>,
+) {
+    let Some(screen) = stack.top() else {
+        return;
+    };
+    let mut send = |action| events.send(MenuEvent { screen, action });
+
+    for mut menu in &mut menus {
+        let entities = menu.items.clone();
+        let is_enabled = |index: usize| enabled.get(entities[index]).is_ok_and(|item| item.enabled);
+        let count = entities.len();
+
+        // Step over disabled items, wrapping around at the ends. Moving up is
+        // a step of `count - 1`, which an empty menu can't take.
+        let step = if actions.just_pressed(Action::MoveUp) {
+            count.saturating_sub(1)
+        } else if actions.just_pressed(Action::MoveDown) {
+            1
+        } else {
+            0
+        };
+        if step != 0 {
+            let mut selected = menu.selected;
+            for _ in 0..count {
+                selected = (selected + step) % count;
+                if is_enabled(selected) {
+                    break;
+                }
+            }
+            menu.selected = selected;
+        }
+
+        for (index, &entity) in entities.iter().enumerate() {
+            let Ok((item, interaction)) = items.get(entity) else {
+                continue;
+            };
+
+            match interaction {
+                Interaction::Hovered if item.enabled => menu.selected = index,
+                Interaction::Clicked if item.enabled => send(MenuAction::Confirm(entity)),
+                _ => {}
+            }
+        }
+
+        let Some(&selected) = entities.get(menu.selected) else {
+            continue;
+        };
+
+        if actions.just_pressed(Action::Confirm) && is_enabled(menu.selected) {
+            send(MenuAction::Confirm(selected));
+        }
+        if actions.just_pressed(Action::MoveLeft) {
+            send(MenuAction::Adjust(selected, -1.0));
+        }
+        if actions.just_pressed(Action::MoveRight) {
+            send(MenuAction::Adjust(selected, 1.0));
+        }
+        if actions.just_pressed(Action::Back) {
+            send(MenuAction::Back);
+        }
+    }
+}
+
+fn highlight_menu_items(
+    menus: Query<&Menu, Changed<Menu>>,
+    mut items: Query<(&MenuItem, &mut Text)>,
+) {
+    for menu in &menus {
+        for (index, entity) in menu.items.iter().enumerate() {
+            let Ok((item, mut text)) = items.get_mut(*entity) else {
+                continue;
+            };
+
+            let color = if !item.enabled {
+                DISABLED_ITEM_COLOR
+            } else if index == menu.selected {
+                SELECTED_ITEM_COLOR
+            } else {
+                ITEM_COLOR
+            };
+
+            for section in text.sections.iter_mut() {
+                section.style.color = color;
+            }
+        }
+    }
+}
+
+fn open_title(mut stack: ResMut<MenuStack>) {
+    stack.0 = vec![MenuScreen::Title];
+}
+
+fn close_menus(mut stack: ResMut<MenuStack>) {
+    stack.0.clear();
+}
+
+/// Replaces the menu on screen with the one at the top of the stack.
+#[allow(clippy::too_many_arguments)]
+fn show_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stack: Res<MenuStack>,
+    roots: Query<Entity, With<MenuRoot>>,
+    stats: Res<LevelStats>,
+    settings: Res<Settings>,
+    // The levels aren't set up until loading has finished, or at all if it
+    // failed.
+    levels: Option<Res<Levels>>,
+    graph: Option<Res<LevelGraph>>,
+    records: Res<LevelRecords>,
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+) {
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+
+    match stack.top() {
+        Some(MenuScreen::Title) => spawn_title(&mut commands, &asset_server, &stats),
+        Some(MenuScreen::Settings) => spawn_settings(&mut commands, &asset_server, &settings),
+        Some(MenuScreen::Controls) => {
+            controls::spawn_controls_menu(&mut commands, &asset_server, &input_map, &rebinding)
+        }
+        Some(MenuScreen::Pause) => pause::spawn_pause_menu(&mut commands, &asset_server),
+        Some(MenuScreen::Slots) => save::spawn_slots_menu(&mut commands, &asset_server, &settings),
+        Some(MenuScreen::LevelSelect) => match (levels, graph) {
+            (Some(levels), Some(graph)) => level_select::spawn_level_select(
+                &mut commands,
+                &asset_server,
+                &levels,
+                &graph,
+                &records,
+            ),
+            _ => warn!("Can't show the level select before the levels are loaded"),
+        },
+        None => {}
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum TitleItem {
+    Continue,
+    NewGame,
+    LoadGame,
+    LevelSelect,
+    Settings,
+    Quit,
+}
+
+fn spawn_title(commands: &mut Commands, asset_server: &AssetServer, stats: &LevelStats) {
+    spawn_menu(commands, asset_server, TITLE, |items| {
+        items.add("Continue", stats.in_progress(), TitleItem::Continue);
+        items.add("New Game", true, TitleItem::NewGame);
+        items.add("Load Game", true, TitleItem::LoadGame);
+        items.add("Level Select", true, TitleItem::LevelSelect);
+        items.add("Settings", true, TitleItem::Settings);
+
+        // There's nothing to quit to on the web.
+        if cfg!(not(target_arch = "wasm32")) {
+            items.add("Quit", true, TitleItem::Quit);
+        }
+    });
+}
+
+fn handle_title(
+    mut events: EventReader<MenuEvent>,
+    items: Query<&TitleItem>,
+    mut stack: ResMut<MenuStack>,
+    mut progress: LevelProgress,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for action in actions_in(&mut events, MenuScreen::Title) {
+        let MenuAction::Confirm(item) = action else {
+            continue;
+        };
+
+        match items.get(item) {
+            Ok(TitleItem::Continue) => next_state.set(GameState::LoadingLevel),
+            Ok(TitleItem::NewGame) => {
+                progress.reset();
+                next_state.set(GameState::LoadingLevel);
+            }
+            Ok(TitleItem::LoadGame) => stack.0.push(MenuScreen::Slots),
+            Ok(TitleItem::LevelSelect) => stack.0.push(MenuScreen::LevelSelect),
+            Ok(TitleItem::Settings) => stack.0.push(MenuScreen::Settings),
+            Ok(TitleItem::Quit) => exit.send(AppExit),
+            Err(_) => {}
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum SettingsItem {
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    PixelPerfect,
+    Controls,
+    Back,
+}
+
+impl SettingsItem {
+    fn label(self, settings: &Settings) -> String {
+        let percent = |volume: f32| (volume * 100.0).round() as u32;
+
+        match self {
+            SettingsItem::MusicVolume => format!("Music: {}%", percent(settings.music_volume)),
+            SettingsItem::SfxVolume => format!("Sound: {}%", percent(settings.sfx_volume)),
+            SettingsItem::Fullscreen if settings.fullscreen => "Fullscreen: On".to_string(),
+            SettingsItem::Fullscreen => "Fullscreen: Off".to_string(),
+            SettingsItem::PixelPerfect if settings.pixel_perfect => "Pixel Perfect: On".to_string(),
+            SettingsItem::PixelPerfect => "Pixel Perfect: Off".to_string(),
+            SettingsItem::Controls => "Controls".to_string(),
+            SettingsItem::Back => "Back".to_string(),
+        }
+    }
+}
+
+fn spawn_settings(commands: &mut Commands, asset_server: &AssetServer, settings: &Settings) {
+    spawn_menu(commands, asset_server, "Settings", |items| {
+        for item in [
+            SettingsItem::MusicVolume,
+            SettingsItem::SfxVolume,
+            SettingsItem::Fullscreen,
+            SettingsItem::PixelPerfect,
+            SettingsItem::Controls,
+            SettingsItem::Back,
+        ] {
+            items.add(item.label(settings), true, item);
+        }
+    });
+}
+
+fn handle_settings(
+    mut events: EventReader<MenuEvent>,
+    items: Query<&SettingsItem>,
+    mut stack: ResMut<MenuStack>,
+    mut settings: ResMut<Settings>,
+) {
+    for action in actions_in(&mut events, MenuScreen::Settings) {
+        let (item, direction) = match action {
+            MenuAction::Back => {
+                stack.0.pop();
+                continue;
+            }
+            MenuAction::Confirm(item) => (item, 1.0),
+            MenuAction::Adjust(item, direction) => (item, direction),
+        };
+
+        let adjust = |volume: f32| (volume + direction * VOLUME_STEP).clamp(0.0, 1.0);
+
+        match items.get(item) {
+            Ok(SettingsItem::MusicVolume) => settings.music_volume = adjust(settings.music_volume),
+            Ok(SettingsItem::SfxVolume) => settings.sfx_volume = adjust(settings.sfx_volume),
+            Ok(SettingsItem::Fullscreen) => settings.fullscreen = !settings.fullscreen,
+            Ok(SettingsItem::PixelPerfect) => settings.pixel_perfect = !settings.pixel_perfect,
+            Ok(SettingsItem::Controls) if matches!(action, MenuAction::Confirm(_)) => {
+                stack.0.push(MenuScreen::Controls);
+            }
+            Ok(SettingsItem::Back) if matches!(action, MenuAction::Confirm(_)) => {
+                stack.0.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_settings_labels(settings: Res<Settings>, mut items: Query<(&SettingsItem, &mut Text)>) {
+    for (item, mut text) in &mut items {
+        text.sections[0].value = item.label(&settings);
+    }
+}
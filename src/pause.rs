@@ -0,0 +1,138 @@
+use bevy::{prelude::*, window::WindowFocused};
+
+use crate::{
+    input::{Action, ActionState},
+    level::LevelProgress,
+    menu::{self, MenuAction, MenuEvent, MenuScreen, MenuStack},
+    GameState,
+};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<PauseState>()
+            // Everything that plays the level stops while paused, the level
+            // is still drawn behind the pause menu.
+            .configure_set(OnUpdate(GameState::Playing).run_if(in_state(PauseState::Running)))
+            // Not in the playing set, it doesn't run while paused.
+            .add_systems(
+                (toggle_pause, pause_on_focus_loss)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_system(handle_pause_menu.in_set(OnUpdate(PauseState::Paused)))
+            .add_system(pause.in_schedule(OnEnter(PauseState::Paused)))
+            .add_system(resume.in_schedule(OnExit(PauseState::Paused)))
+            .add_system(unpause.in_schedule(OnExit(GameState::Playing)));
+    }
+}
+
+#[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum PauseState {
+    #[default]
+    Running,
+    // The level is frozen and the pause menu is showing
+    Paused,
+}
+
+fn toggle_pause(
+    actions: Res<ActionState>,
+    state: Res<State<PauseState>>,
+    stack: Res<MenuStack>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+
+    match state.0 {
+        PauseState::Running => next_state.set(PauseState::Paused),
+        // Going back from the pause menu already resumes, so only resume
+        // here for buttons that aren't also bound to going back.
+        PauseState::Paused
+            if stack.top() == Some(MenuScreen::Pause) && !actions.just_pressed(Action::Back) =>
+        {
+            next_state.set(PauseState::Running);
+        }
+        PauseState::Paused => {}
+    }
+}
+
+fn pause_on_focus_loss(
+    mut events: EventReader<WindowFocused>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if events.iter().any(|event| !event.focused) && state.0 == PauseState::Running {
+        next_state.set(PauseState::Paused);
+    }
+}
+
+fn pause(mut time: ResMut<Time>, mut stack: ResMut<MenuStack>) {
+    time.pause();
+    stack.0 = vec![MenuScreen::Pause];
+}
+
+fn resume(mut time: ResMut<Time>, mut stack: ResMut<MenuStack>) {
+    time.unpause();
+
+    // Quitting to the title screen may have already replaced the menus.
+    if stack.0.first() == Some(&MenuScreen::Pause) {
+        stack.0.clear();
+    }
+}
+
+/// Leaving the level doesn't go through the pause menu, so make sure the
+/// game isn't still paused when it's played again.
+fn unpause(state: Res<State<PauseState>>, mut next_state: ResMut<NextState<PauseState>>) {
+    if state.0 == PauseState::Paused {
+        next_state.set(PauseState::Running);
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseItem {
+    Resume,
+    RestartLevel,
+    Settings,
+    QuitToMenu,
+}
+
+pub fn spawn_pause_menu(commands: &mut Commands, asset_server: &AssetServer) {
+    menu::spawn_menu(commands, asset_server, "Paused", |items| {
+        items.add("Resume", true, PauseItem::Resume);
+        items.add("Restart Level", true, PauseItem::RestartLevel);
+        items.add("Settings", true, PauseItem::Settings);
+        items.add("Quit to Menu", true, PauseItem::QuitToMenu);
+    });
+}
+
+fn handle_pause_menu(
+    mut events: EventReader<MenuEvent>,
+    items: Query<&PauseItem>,
+    mut stack: ResMut<MenuStack>,
+    mut progress: LevelProgress,
+    mut next_state: ResMut<NextState<PauseState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    for action in menu::actions_in(&mut events, MenuScreen::Pause) {
+        let item = match action {
+            MenuAction::Back => PauseItem::Resume,
+            MenuAction::Confirm(item) => match items.get(item) {
+                Ok(item) => *item,
+                Err(_) => continue,
+            },
+            MenuAction::Adjust(..) => continue,
+        };
+
+        match item {
+            PauseItem::Resume => next_state.set(PauseState::Running),
+            PauseItem::RestartLevel => {
+                progress.restart_level();
+                next_game_state.set(GameState::Transitioning);
+            }
+            PauseItem::Settings => stack.0.push(MenuScreen::Settings),
+            PauseItem::QuitToMenu => next_game_state.set(GameState::Menu),
+        }
+    }
+}
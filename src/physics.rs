@@ -8,6 +8,12 @@ use crate::{
     GameState,
 };
 
+/// Downward acceleration applied to every [`Actor`] each frame.
+const GRAVITY: f32 = 0.5;
+/// The fastest an actor can fall, kept below the tile size so actors can't
+/// tunnel through the ground.
+const MAX_FALL_SPEED: f32 = 12.0;
+
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
@@ -15,7 +21,7 @@ impl Plugin for PhysicsPlugin {
         app.add_systems(
             (apply_gravity, update)
                 .chain()
-                .after(player::handle_input)
+                .after(player::apply_movement)
                 .in_set(OnUpdate(GameState::Playing)),
         );
     }
@@ -32,27 +38,66 @@ pub struct Collider {
     pub size: Vec2,
 }
 
-fn apply_gravity(mut actors: Query<&mut Velocity, With<Actor>>) {
-    for mut _velocity in &mut actors {
-        
-        //velocity.y = (velocity.y - 1.0);//.max(-32.0);
+/// Scales how strongly gravity affects an actor. Actors without this
+/// component use a scale of `1.0`.
+#[derive(Component)]
+pub struct GravityScale(pub f32);
+
+impl Default for GravityScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Which sides of an actor are touching a solid, updated after every move.
+#[derive(Component, Default, Debug)]
+pub struct Contacts {
+    pub ground: bool,
+    pub ceiling: bool,
+    pub left_wall: bool,
+    pub right_wall: bool,
+}
+
+type ActorQuery<'w, 's, 'a> = Query<
+    'w,
+    's,
+    (
+        &'a mut Velocity,
+        &'a mut Transform,
+        &'a Collider,
+        Option<&'a mut Contacts>,
+    ),
+    (With<Actor>, Without<Solid>),
+>;
+
+type SolidQuery<'w, 's, 'a> =
+    Query<'w, 's, (&'a Transform, &'a Collider), (Without<Actor>, With<Solid>)>;
+
+fn apply_gravity(mut actors: Query<(&mut Velocity, Option<&GravityScale>), With<Actor>>) {
+    for (mut velocity, scale) in &mut actors {
+        let scale = scale.map_or(1.0, |scale| scale.0);
+
+        velocity.y = (velocity.y - GRAVITY * scale).max(-MAX_FALL_SPEED);
     }
 }
 
-fn update(
-    mut actors: Query<(&Velocity, &mut Transform, &Collider), (With<Actor>, Without<Solid>)>,
-    solids: Query<(&Transform, &Collider), (Without<Actor>, With<Solid>)>,
-) {
-    for (velocity, mut actor_transform, actor_collider) in &mut actors {
+pub(crate) fn update(mut actors: ActorQuery, solids: SolidQuery) {
+    for (mut velocity, mut actor_transform, actor_collider, contacts) in &mut actors {
         actor_transform.translation.x += velocity.x;
+        // Taken before resolving so every solid pushes the actor back the
+        // same way, even after an earlier one has stopped it.
+        let sign = velocity.x.signum();
+        let mut blocked = false;
 
         for (transform, collider) in &solids {
-            if let Some(_) = collide_aabb::collide(
+            if collide_aabb::collide(
                 actor_transform.translation,
                 actor_collider.size,
                 transform.translation,
                 collider.size,
-            ) {
+            )
+            .is_some()
+            {
                 let actor_pos = actor_transform.translation;
                 let actor_size = actor_collider.size;
 
@@ -66,21 +111,28 @@ fn update(
                     solid_pos.x + (solid_size.x / 2.0),
                 );
 
-                let sign = velocity.x.signum();
-
                 actor_transform.translation.x += amount * -sign;
+                blocked = true;
             }
         }
 
+        if blocked {
+            velocity.x = 0.0;
+        }
+
         actor_transform.translation.y += velocity.y;
+        let sign = velocity.y.signum();
+        let mut blocked = false;
 
         for (transform, collider) in &solids {
-            if let Some(_) = collide_aabb::collide(
+            if collide_aabb::collide(
                 actor_transform.translation,
                 actor_collider.size,
                 transform.translation,
                 collider.size,
-            ) {
+            )
+            .is_some()
+            {
                 let actor_pos = actor_transform.translation;
                 let actor_size = actor_collider.size;
 
@@ -94,14 +146,37 @@ fn update(
                     solid_pos.y + (solid_size.y / 2.0),
                 );
 
-                let sign = velocity.y.signum();
-
                 actor_transform.translation.y += amount * -sign;
+                blocked = true;
             }
         }
+
+        if blocked {
+            velocity.y = 0.0;
+        }
+
+        if let Some(mut contacts) = contacts {
+            let position = actor_transform.translation;
+            let size = actor_collider.size;
+
+            contacts.ground = touching(position, size, Vec2::NEG_Y, &solids);
+            contacts.ceiling = touching(position, size, Vec2::Y, &solids);
+            contacts.left_wall = touching(position, size, Vec2::NEG_X, &solids);
+            contacts.right_wall = touching(position, size, Vec2::X, &solids);
+        }
     }
 }
 
+/// Checks if nudging an actor one pixel in `direction` would put it inside a
+/// solid.
+fn touching(position: Vec3, size: Vec2, direction: Vec2, solids: &SolidQuery) -> bool {
+    let probe = position + direction.extend(0.0);
+
+    solids.iter().any(|(transform, collider)| {
+        collide_aabb::collide(probe, size, transform.translation, collider.size).is_some()
+    })
+}
+
 fn overlap(min_1: f32, max_1: f32, min_2: f32, max_2: f32) -> f32 {
     (0.0_f32).max((max_1).min(max_2) - (min_1).max(min_2))
 }
@@ -0,0 +1,225 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        texture::ImageSampler,
+        view::RenderLayers,
+    },
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+
+use crate::{camera::GameCamera, settings::Settings, GameState};
+
+/// The size of the view the game is drawn at in pixel perfect mode, before
+/// it's scaled up to fit the window. This is exactly half of the default
+/// window size.
+const VIRTUAL_WIDTH: u32 = 400;
+const VIRTUAL_HEIGHT: u32 = 300;
+/// The layer the scaled up view is drawn on, so the game camera doesn't see
+/// it.
+const SCREEN_LAYER: u8 = 1;
+const LETTERBOX_COLOR: Color = Color::BLACK;
+
+pub struct PixelPerfectPlugin;
+
+impl Plugin for PixelPerfectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LowResTarget>()
+            .add_system(spawn_screen.in_schedule(OnEnter(GameState::Loading)))
+            .add_systems((apply_render_mode, scale_screen).chain())
+            .add_system(
+                snap_to_pixels
+                    .run_if(|settings: Res<Settings>| settings.pixel_perfect)
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// The low resolution image the game camera draws to in pixel perfect mode.
+#[derive(Resource)]
+struct LowResTarget(Handle<Image>);
+
+impl FromWorld for LowResTarget {
+    fn from_world(world: &mut World) -> Self {
+        let size = Extent3d {
+            width: VIRTUAL_WIDTH,
+            height: VIRTUAL_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: Some("low_res_target"),
+                size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Bgra8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            sampler_descriptor: ImageSampler::nearest(),
+            ..default()
+        };
+        image.resize(size);
+
+        Self(world.resource_mut::<Assets<Image>>().add(image))
+    }
+}
+
+/// The camera that draws the low resolution view to the window, along with
+/// the UI.
+#[derive(Component)]
+struct ScreenCamera;
+
+/// The sprite showing the low resolution view, scaled up to fit the window.
+#[derive(Component)]
+struct Screen;
+
+fn spawn_screen(mut commands: Commands, target: Res<LowResTarget>) {
+    let layer = RenderLayers::layer(SCREEN_LAYER);
+
+    commands.spawn((
+        ScreenCamera,
+        Camera2dBundle {
+            camera: Camera {
+                // Drawn after the game camera has filled in the view.
+                order: 1,
+                is_active: false,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(LETTERBOX_COLOR),
+            },
+            ..default()
+        },
+        layer,
+    ));
+
+    commands.spawn((
+        Screen,
+        SpriteBundle {
+            texture: target.0.clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        layer,
+    ));
+}
+
+/// The game camera, which draws to either the window or the low resolution
+/// view.
+type GameCameraFilter = (With<GameCamera>, Without<ScreenCamera>);
+/// Everything that's drawn at a position in the world.
+type SnappedFilter = (
+    Or<(With<Sprite>, With<TextureAtlasSprite>, With<GameCamera>)>,
+    Without<Screen>,
+);
+
+/// Switches the game camera between drawing straight to the window and
+/// drawing to the low resolution view.
+fn apply_render_mode(
+    settings: Res<Settings>,
+    target: Res<LowResTarget>,
+    added: Query<(), Added<GameCamera>>,
+    mut game_camera: Query<(Entity, &mut Camera), GameCameraFilter>,
+    mut screen_camera: Query<&mut Camera, With<ScreenCamera>>,
+    mut screen: Query<&mut Visibility, With<Screen>>,
+    mut commands: Commands,
+) {
+    if !settings.is_changed() && added.is_empty() {
+        return;
+    }
+    let enabled = settings.pixel_perfect;
+
+    for (entity, mut camera) in &mut game_camera {
+        camera.target = if enabled {
+            RenderTarget::Image(target.0.clone())
+        } else {
+            RenderTarget::default()
+        };
+        // The UI stays at the window's resolution, so it's drawn by the
+        // screen camera instead.
+        commands
+            .entity(entity)
+            .insert(UiCameraConfig { show_ui: !enabled });
+    }
+
+    for mut camera in &mut screen_camera {
+        camera.is_active = enabled;
+    }
+
+    for mut visibility in &mut screen {
+        *visibility = if enabled {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Scales the low resolution view up by the largest whole number that fits
+/// the window, leaving the rest as a letterbox.
+fn scale_screen(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut screen: Query<&mut Transform, With<Screen>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    // Measured in physical pixels, scaling by a whole number of logical
+    // pixels isn't enough on high DPI screens.
+    let width = window.physical_width();
+    let height = window.physical_height();
+    let scale = (width / VIRTUAL_WIDTH).min(height / VIRTUAL_HEIGHT).max(1);
+
+    // When the leftover space can't be split evenly the view is nudged half
+    // a pixel so its edges still line up with the window's pixels.
+    let nudge = |space: u32, size: u32| {
+        if space.saturating_sub(size * scale) % 2 == 1 {
+            0.5
+        } else {
+            0.0
+        }
+    };
+
+    let scale_factor = window.scale_factor() as f32;
+    let translation = Vec3::new(
+        nudge(width, VIRTUAL_WIDTH),
+        nudge(height, VIRTUAL_HEIGHT),
+        0.0,
+    ) / scale_factor;
+    let scale = scale as f32 / scale_factor;
+    let scale = Vec3::new(scale, scale, 1.0);
+
+    for mut transform in &mut screen {
+        if transform.translation != translation || transform.scale != scale {
+            transform.translation = translation;
+            transform.scale = scale;
+        }
+    }
+}
+
+/// Rounds where sprites and the game camera are drawn to whole pixels, so
+/// nothing shimmers as the camera moves. Their actual positions are left
+/// alone so movement isn't affected.
+fn snap_to_pixels(mut transforms: Query<&mut GlobalTransform, SnappedFilter>) {
+    for mut transform in &mut transforms {
+        let mut affine = transform.affine();
+        let rounded = affine.translation.round();
+
+        if affine.translation != rounded {
+            affine.translation = rounded;
+            *transform = affine.into();
+        }
+    }
+}
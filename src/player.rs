@@ -1,17 +1,64 @@
 use bevy::prelude::*;
 
 use crate::{
-    physics::{Actor, Collider},
-    GameState,
+    animation::{AnimationSheet, AnimationSheets, Animator},
+    health::Health,
+    input::{Action, ActionState},
+    level::{self, PlayerSpawn},
+    physics::{Actor, Collider, Contacts, GravityScale},
+    transition, GameState,
 };
 
+const MAX_HEALTH: u32 = 3;
+/// Horizontal speed the player accelerates towards while walking.
+const WALK_SPEED: f32 = 6.0;
+/// How much horizontal speed is gained or lost per frame.
+const WALK_ACCELERATION: f32 = 1.0;
+/// Upward speed applied when a jump starts.
+const JUMP_SPEED: f32 = 10.0;
+/// Upward speed the player is capped at once the jump button is released.
+const JUMP_CUT_SPEED: f32 = 3.0;
+/// Fastest the player falls while sliding down a wall.
+const WALL_SLIDE_SPEED: f32 = 2.0;
+/// Velocity applied when jumping off a wall, pushing away from it.
+const WALL_JUMP_SPEED: Vec2 = Vec2::new(6.0, 9.0);
+/// How long horizontal input is ignored after a wall jump so the player can't
+/// immediately steer back into the wall.
+const WALL_JUMP_LOCK: f32 = 0.15;
+const DASH_SPEED: f32 = 12.0;
+const DASH_DURATION: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 0.6;
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(spawn_player.in_schedule(OnEnter(GameState::Playing)))
-            .add_system(handle_input.in_set(OnUpdate(GameState::Playing)))
-            .add_system(despawn_player.in_schedule(OnExit(GameState::Playing)));
+        app.add_event::<PlayerStateChanged>()
+            .add_system(
+                spawn_player
+                    .after(level::spawn_map)
+                    .in_schedule(OnEnter(GameState::Playing)),
+            )
+            .add_systems(
+                (handle_input, update_state, apply_movement)
+                    .chain()
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(
+                animate_player
+                    .after(update_state)
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            // The player sticks around while dying so the death sequence can
+            // play out.
+            .add_system(
+                despawn_player
+                    .run_if(transition::screen_covered)
+                    .in_set(OnUpdate(GameState::Transitioning)),
+            )
+            .add_system(despawn_player.in_schedule(OnExit(GameState::Dying)))
+            // Quitting from the pause menu leaves the player behind.
+            .add_system(despawn_player.in_schedule(OnEnter(GameState::Menu)));
     }
 }
 
@@ -30,6 +77,68 @@ pub struct Acceleration {
 #[derive(Component, Default)]
 pub struct Player;
 
+/// What the player is currently doing. Each state has its own movement rules
+/// in [`apply_movement`] and the transitions between them all live in
+/// [`update_state`].
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayerState {
+    #[default]
+    Idle,
+    Walking,
+    Jumping,
+    Falling,
+    WallSliding,
+    Dashing,
+}
+
+/// Sent whenever the player's [`PlayerState`] changes so animation and audio
+/// can react to it.
+pub struct PlayerStateChanged {
+    pub from: PlayerState,
+    pub to: PlayerState,
+}
+
+/// The player's intent for this frame, filled in from their bound actions.
+#[derive(Component, Default)]
+pub struct PlayerInput {
+    /// Horizontal movement in the range `-1.0..=1.0`.
+    pub movement: f32,
+    pub jump_pressed: bool,
+    pub jump_held: bool,
+    pub dash_pressed: bool,
+}
+
+/// Book-keeping for the movement abilities that outlast a single frame.
+#[derive(Component)]
+pub struct PlayerMovement {
+    /// `1.0` when facing right, `-1.0` when facing left.
+    pub facing: f32,
+    dash: Timer,
+    dash_cooldown: Timer,
+    /// Only one dash is allowed until the player touches the ground or a wall.
+    can_dash: bool,
+    wall_jump_lock: Timer,
+}
+
+impl Default for PlayerMovement {
+    fn default() -> Self {
+        let mut dash_cooldown = Timer::from_seconds(DASH_COOLDOWN, TimerMode::Once);
+        // The player should be able to dash straight away.
+        dash_cooldown.tick(dash_cooldown.duration());
+
+        let mut wall_jump_lock = Timer::from_seconds(WALL_JUMP_LOCK, TimerMode::Once);
+        wall_jump_lock.tick(wall_jump_lock.duration());
+
+        Self {
+            facing: 1.0,
+            dash: Timer::from_seconds(DASH_DURATION, TimerMode::Once),
+            dash_cooldown,
+            can_dash: true,
+            wall_jump_lock,
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct PlayerBundle {
     player: Player,
@@ -37,67 +146,280 @@ pub struct PlayerBundle {
     velocity: Velocity,
     acceleration: Acceleration,
     collider: Collider,
+    contacts: Contacts,
+    gravity: GravityScale,
+    state: PlayerState,
+    input: PlayerInput,
+    movement: PlayerMovement,
+    health: Health,
+    animator: Animator,
     #[bundle]
-    sprite: SpriteBundle,
+    sprite: SpriteSheetBundle,
 }
 
-impl Default for PlayerBundle {
-    fn default() -> Self {
+impl PlayerBundle {
+    pub fn new(sheet: Handle<AnimationSheet>, sheets: &Assets<AnimationSheet>) -> Self {
+        let atlas = sheets
+            .get(&sheet)
+            .expect("Player animation sheet isn't loaded")
+            .atlas
+            .clone();
+
         Self {
             player: Player,
             actor: Actor,
             collider: Collider {
                 size: Vec2::new(32.0, 32.0),
             },
-            sprite: SpriteBundle::default(),
+            sprite: SpriteSheetBundle {
+                texture_atlas: atlas,
+                ..default()
+            },
             velocity: Velocity::default(),
             acceleration: Acceleration::default(),
+            contacts: Contacts::default(),
+            gravity: GravityScale::default(),
+            state: PlayerState::default(),
+            input: PlayerInput::default(),
+            movement: PlayerMovement::default(),
+            health: Health::new(MAX_HEALTH),
+            animator: Animator::new(sheet, "idle"),
         }
     }
 }
 
-fn spawn_player(mut commands: Commands) {
-    commands.spawn(PlayerBundle {
-        sprite: SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-            sprite: Sprite {
-                color: Color::RED,
-                custom_size: Some(Vec2::new(32.0, 32.0)),
-                ..default()
-            },
-            ..default()
-        },
-        ..default()
-    });
+fn spawn_player(
+    mut commands: Commands,
+    animation_sheets: Res<AnimationSheets>,
+    sheets: Res<Assets<AnimationSheet>>,
+    spawn: Res<PlayerSpawn>,
+) {
+    let sheet = animation_sheets
+        .get("player")
+        .expect("The player's animation sheet is one of `ANIMATION_SHEETS`");
+    let mut player = PlayerBundle::new(sheet, &sheets);
+
+    // The spawn point marks where the player's feet go.
+    let position = spawn.0 + Vec2::new(0.0, player.collider.size.y / 2.0);
+    player.sprite.transform = Transform::from_translation(position.extend(0.0));
+
+    commands.spawn(player);
 }
 
 pub(crate) fn handle_input(
-    input: Res<Input<KeyCode>>,
-    mut player: Query<&mut Velocity, With<Player>>,
+    actions: Res<ActionState>,
+    mut player: Query<&mut PlayerInput, With<Player>>,
 ) {
-    let mut velocity = player.single_mut();
+    let mut input = player.single_mut();
 
-    if input.pressed(KeyCode::A) {
-        velocity.x = (velocity.x - 1.0).max(-12.0);
-    } else if input.pressed(KeyCode::D) {
-        velocity.x = (velocity.x + 1.0).min(12.0);
-    } else {
-        velocity.x = 0.0;
+    input.movement = actions.axis(Action::MoveLeft, Action::MoveRight);
+    input.jump_pressed = actions.just_pressed(Action::Jump);
+    input.jump_held = actions.pressed(Action::Jump);
+    input.dash_pressed = actions.just_pressed(Action::Dash);
+}
+
+/// Moves the player between states based on their input and what they're
+/// touching. Anything that should only happen once when entering a state,
+/// like the initial jump impulse, happens here.
+pub(crate) fn update_state(
+    time: Res<Time>,
+    mut player: Query<
+        (
+            &mut PlayerState,
+            &mut PlayerMovement,
+            &mut Velocity,
+            &PlayerInput,
+            &Contacts,
+        ),
+        With<Player>,
+    >,
+    mut state_changed: EventWriter<PlayerStateChanged>,
+) {
+    let (mut state, mut movement, mut velocity, input, contacts) = player.single_mut();
+
+    movement.dash.tick(time.delta());
+    movement.dash_cooldown.tick(time.delta());
+    movement.wall_jump_lock.tick(time.delta());
+
+    if input.movement != 0.0 && *state != PlayerState::Dashing {
+        movement.facing = input.movement.signum();
+    }
+
+    if contacts.ground || *state == PlayerState::WallSliding {
+        movement.can_dash = true;
     }
 
-    if input.pressed(KeyCode::W) {
-        velocity.y = (velocity.y + 1.0).min(32.0);
-    } else if input.pressed(KeyCode::S) {
-        velocity.y = (velocity.y - 1.0).max(-32.0);
+    let wall = if contacts.left_wall {
+        Some(-1.0)
+    } else if contacts.right_wall {
+        Some(1.0)
     } else {
-        velocity.y = 0.0;
+        None
+    };
+
+    let next = match *state {
+        _ if input.dash_pressed
+            && *state != PlayerState::Dashing
+            && movement.can_dash
+            && movement.dash_cooldown.finished() =>
+        {
+            movement.dash.reset();
+            movement.dash_cooldown.reset();
+            movement.can_dash = contacts.ground;
+
+            velocity.x = movement.facing * DASH_SPEED;
+            velocity.y = 0.0;
+
+            PlayerState::Dashing
+        }
+        PlayerState::Dashing if !movement.dash.finished() => PlayerState::Dashing,
+        PlayerState::WallSliding if input.jump_pressed => {
+            // `wall` is always set while sliding, jump away from it.
+            let away = -wall.unwrap_or(movement.facing);
+
+            velocity.x = away * WALL_JUMP_SPEED.x;
+            velocity.y = WALL_JUMP_SPEED.y;
+            movement.facing = away;
+            movement.wall_jump_lock.reset();
+
+            PlayerState::Jumping
+        }
+        _ if contacts.ground && input.jump_pressed => {
+            velocity.y = JUMP_SPEED;
+
+            PlayerState::Jumping
+        }
+        _ if contacts.ground => {
+            if input.movement != 0.0 {
+                PlayerState::Walking
+            } else {
+                PlayerState::Idle
+            }
+        }
+        // Only slide when falling and pushing into the wall.
+        _ if velocity.y <= 0.0
+            && input.movement != 0.0
+            && wall == Some(input.movement.signum()) =>
+        {
+            PlayerState::WallSliding
+        }
+        PlayerState::Jumping if velocity.y > 0.0 => PlayerState::Jumping,
+        _ => PlayerState::Falling,
+    };
+
+    if next != *state {
+        state_changed.send(PlayerStateChanged {
+            from: *state,
+            to: next,
+        });
+        *state = next;
     }
-    //println!("({}, {})", velocity.x, velocity.y);
 }
 
-fn despawn_player(mut commands: Commands, player: Query<Entity, With<Player>>) {
-    let player = player.single();
+/// Applies each state's movement rules to the player's velocity.
+pub(crate) fn apply_movement(
+    mut player: Query<
+        (
+            &PlayerState,
+            &PlayerMovement,
+            &PlayerInput,
+            &mut Velocity,
+            &mut GravityScale,
+        ),
+        With<Player>,
+    >,
+) {
+    let (state, movement, input, mut velocity, mut gravity) = player.single_mut();
 
-    // using despawn_recursive just in case we ever give the player `Childern`
-    commands.entity(player).despawn_recursive();
+    gravity.0 = 1.0;
+
+    match state {
+        PlayerState::Idle | PlayerState::Walking | PlayerState::Falling => {
+            walk(&mut velocity, input.movement);
+        }
+        PlayerState::Jumping => {
+            if movement.wall_jump_lock.finished() {
+                walk(&mut velocity, input.movement);
+            }
+
+            // Letting go of jump early cuts the jump short.
+            if !input.jump_held {
+                velocity.y = velocity.y.min(JUMP_CUT_SPEED);
+            }
+        }
+        PlayerState::WallSliding => {
+            velocity.x = input.movement;
+            velocity.y = velocity.y.max(-WALL_SLIDE_SPEED);
+        }
+        PlayerState::Dashing => {
+            gravity.0 = 0.0;
+            velocity.x = movement.facing * DASH_SPEED;
+            velocity.y = 0.0;
+        }
+    }
+}
+
+/// The clip that's played for the duration of each state.
+fn state_clip(state: PlayerState) -> &'static str {
+    match state {
+        PlayerState::Idle => "idle",
+        PlayerState::Walking | PlayerState::Dashing => "run",
+        PlayerState::Jumping => "jump",
+        PlayerState::Falling | PlayerState::WallSliding => "fall",
+    }
+}
+
+fn animate_player(
+    mut state_changed: EventReader<PlayerStateChanged>,
+    mut player: Query<
+        (
+            &PlayerState,
+            &Velocity,
+            &mut Animator,
+            &mut TextureAtlasSprite,
+        ),
+        With<Player>,
+    >,
+) {
+    let (state, velocity, mut animator, mut sprite) = player.single_mut();
+
+    for change in state_changed.iter() {
+        let landed = matches!(change.from, PlayerState::Falling | PlayerState::WallSliding);
+
+        if landed && change.to == PlayerState::Idle {
+            animator.play("land");
+        } else {
+            animator.play(state_clip(change.to));
+        }
+    }
+
+    // One-shot clips like landing hand back to the current state's clip once
+    // they're done.
+    if animator.finished() && animator.clip() != state_clip(*state) {
+        animator.play(state_clip(*state));
+    }
+
+    // The spritesheet faces right.
+    if velocity.x.abs() > f32::EPSILON {
+        sprite.flip_x = velocity.x < 0.0;
+    }
+}
+
+/// Accelerates `velocity` towards walking speed in the direction of
+/// `movement`, or brings it to a stop when there's no input.
+fn walk(velocity: &mut Velocity, movement: f32) {
+    let target = movement * WALK_SPEED;
+
+    if velocity.x < target {
+        velocity.x = (velocity.x + WALK_ACCELERATION).min(target);
+    } else if velocity.x > target {
+        velocity.x = (velocity.x - WALK_ACCELERATION).max(target);
+    }
+}
+
+fn despawn_player(mut commands: Commands, player: Query<Entity, With<Player>>) {
+    for player in &player {
+        // using despawn_recursive just in case we ever give the player `Childern`
+        commands.entity(player).despawn_recursive();
+    }
 }
@@ -0,0 +1,445 @@
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    checkpoint::{ActiveCheckpoint, CheckpointData, LevelObjectState},
+    completion, config,
+    level::{CurrentLevel, LevelRecords, LevelStats, Levels},
+    level_graph::LevelGraph,
+    menu::{self, MenuAction, MenuEvent, MenuScreen, MenuStack},
+    pause::PauseState,
+    settings::Settings,
+    GameState,
+};
+
+/// How many games can be saved at once.
+pub const SAVE_SLOTS: usize = 3;
+/// Bumped whenever [`SaveData`] changes in a way older saves can't be read
+/// as, along with adding a migration for the old version.
+const SAVE_VERSION: u32 = 1;
+/// Upgrades older saves to the current version. `MIGRATIONS[n]` turns a
+/// version `n + 1` save into a version `n + 2` one.
+const MIGRATIONS: [fn(&mut Value); SAVE_VERSION as usize - 1] = [];
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadedSlot>()
+            .add_system(
+                // The levels aren't there if they failed to load, there's
+                // nothing to load the save into.
+                load_slot
+                    .run_if(resource_exists::<Levels>())
+                    .in_schedule(OnEnter(GameState::Menu)),
+            )
+            .add_system(
+                save_game
+                    .run_if(
+                        resource_changed::<CurrentLevel>()
+                            .or_else(resource_changed::<ActiveCheckpoint>())
+                            .or_else(resource_changed::<LevelObjectState>()),
+                    )
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            // Picks up the time and deaths since the last save, the player
+            // might close the game from here.
+            .add_system(save_game.in_schedule(OnExit(GameState::Playing)))
+            .add_system(save_game.in_schedule(OnEnter(PauseState::Paused)))
+            .add_system(save_game.in_schedule(OnExit(GameState::Complete)))
+            .add_system(handle_slots_menu.in_set(OnUpdate(GameState::Menu)));
+    }
+}
+
+/// Everything that's kept in a save slot.
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    /// The level to continue from.
+    pub level: String,
+    pub entrance: Option<String>,
+    pub checkpoint: Option<CheckpointData>,
+    pub objects: LevelObjectState,
+    pub stats: LevelStats,
+    pub records: LevelRecords,
+}
+
+/// The slot the game being played was loaded from and is saved to.
+#[derive(Resource, Default)]
+struct LoadedSlot(Option<usize>);
+
+/// Reads and writes the player's progress to save slots.
+#[derive(SystemParam)]
+pub struct SaveGame<'w> {
+    loaded: ResMut<'w, LoadedSlot>,
+    levels: Res<'w, Levels>,
+    graph: Res<'w, LevelGraph>,
+    current_level: ResMut<'w, CurrentLevel>,
+    stats: ResMut<'w, LevelStats>,
+    records: ResMut<'w, LevelRecords>,
+    object_state: ResMut<'w, LevelObjectState>,
+    active_checkpoint: ResMut<'w, ActiveCheckpoint>,
+}
+
+impl SaveGame<'_> {
+    /// Replaces the progress being played with what's saved in `slot`, or a
+    /// new game if it's empty.
+    pub fn load(&mut self, slot: usize) {
+        self.loaded.0 = Some(slot);
+
+        let data = match read_slot(slot) {
+            Ok(data) => data,
+            Err(err) => {
+                // Keep the unreadable save around rather than overwriting it
+                // with the new game, it might be recoverable.
+                warn!("Save slot {} can't be read: {}", slot + 1, err);
+                if let Err(err) = back_up(&slot_file(slot)) {
+                    error!("Failed to back up save slot {}: {}", slot + 1, err);
+                    self.loaded.0 = None;
+                }
+                None
+            }
+        };
+        let data = data.unwrap_or_else(|| SaveData {
+            version: SAVE_VERSION,
+            level: self.graph.start().to_string(),
+            entrance: None,
+            checkpoint: None,
+            objects: default(),
+            stats: default(),
+            records: default(),
+        });
+
+        *self.current_level = match self.levels.get(&data.level) {
+            Some(level) => {
+                *self.object_state = data.objects;
+                self.active_checkpoint.0 = data.checkpoint;
+                CurrentLevel::new(level.clone(), data.entrance)
+            }
+            None => {
+                // Levels can be renamed or removed between versions of the
+                // game, start over rather than losing the whole save.
+                warn!(
+                    "Save slot {} is in level '{}' which doesn't exist, starting from the first level",
+                    slot + 1,
+                    data.level
+                );
+                *self.object_state = default();
+                self.active_checkpoint.0 = None;
+                let start = self.levels.get(self.graph.start()).unwrap();
+                CurrentLevel::new(start.clone(), None)
+            }
+        };
+        *self.stats = data.stats;
+        *self.records = data.records;
+    }
+
+    /// Writes the progress being played to the slot it was loaded from.
+    pub fn save(&self) {
+        let Some(slot) = self.loaded.0 else {
+            return;
+        };
+
+        let data = SaveData {
+            version: SAVE_VERSION,
+            level: self.current_level.name().to_string(),
+            entrance: self.current_level.entrance().map(String::from),
+            checkpoint: self.active_checkpoint.0.clone(),
+            objects: self.object_state.clone(),
+            stats: self.stats.clone(),
+            records: self.records.clone(),
+        };
+
+        let result = serde_json::to_string(&data)
+            .map_err(|err| err.to_string())
+            .and_then(|json| write(&slot_file(slot), &json));
+
+        if let Err(err) = result {
+            error!("Failed to save to slot {}: {}", slot + 1, err);
+        }
+    }
+}
+
+fn slot_file(slot: usize) -> String {
+    format!("save_{}.json", slot + 1)
+}
+
+/// Reads the save in `slot`, upgrading it if it's from an older version.
+/// Returns `None` if the slot is empty, or an error if it can't be read.
+pub fn read_slot(slot: usize) -> Result<Option<SaveData>, String> {
+    let Some(contents) = read(&slot_file(slot)) else {
+        return Ok(None);
+    };
+
+    parse_save(&contents).map(Some)
+}
+
+fn parse_save(contents: &str) -> Result<SaveData, String> {
+    let mut value: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    upgrade(&mut value, &MIGRATIONS)?;
+
+    serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+/// Runs the migrations a save needs to be brought up to date. The current
+/// version is the one after the last migration.
+fn upgrade(value: &mut Value, migrations: &[fn(&mut Value)]) -> Result<(), String> {
+    let current = migrations.len() as u64 + 1;
+
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or("it has no version")?;
+    if version == 0 || version > current {
+        return Err(format!("version {} isn't supported", version));
+    }
+
+    for migrate in &migrations[version as usize - 1..] {
+        migrate(value);
+    }
+    value["version"] = current.into();
+
+    Ok(())
+}
+
+/// The platform specific directory save files are stored in, or `None` if it
+/// can't be determined.
+#[cfg(not(target_arch = "wasm32"))]
+fn data_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library/Application Support"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    base.map(|base| base.join(config::APP_DIR))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(name: &str) -> Option<String> {
+    fs::read_to_string(data_dir()?.join(name)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(name: &str, contents: &str) -> Result<(), String> {
+    let dir = data_dir().ok_or("couldn't find a data directory")?;
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    // Write the whole file before replacing the old one, so closing the game
+    // part way through doesn't lose the save.
+    let path = dir.join(name);
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents).map_err(|err| err.to_string())?;
+    fs::rename(&temp, &path).map_err(|err| err.to_string())
+}
+
+/// Moves the file `name` out of the way to `name.bak`, replacing any older
+/// backup.
+#[cfg(not(target_arch = "wasm32"))]
+fn back_up(name: &str) -> Result<(), String> {
+    let dir = data_dir().ok_or("couldn't find a data directory")?;
+    fs::rename(dir.join(name), dir.join(format!("{}.bak", name))).map_err(|err| err.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(name: &str) -> String {
+    format!("{}/{}", config::APP_DIR, name)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(name: &str) -> Option<String> {
+    local_storage()?.get_item(&storage_key(name)).ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(name: &str, contents: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("localStorage isn't available")?
+        .set_item(&storage_key(name), contents)
+        .map_err(|err| format!("{:?}", err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn back_up(name: &str) -> Result<(), String> {
+    let storage = local_storage().ok_or("localStorage isn't available")?;
+    let contents = read(name).ok_or("there's nothing to back up")?;
+
+    storage
+        .set_item(&storage_key(&format!("{}.bak", name)), &contents)
+        .and_then(|_| storage.remove_item(&storage_key(name)))
+        .map_err(|err| format!("{:?}", err))
+}
+
+fn load_slot(settings: Res<Settings>, mut save: SaveGame) {
+    let slot = settings.save_slot.min(SAVE_SLOTS - 1);
+
+    if save.loaded.0 != Some(slot) {
+        save.load(slot);
+    }
+}
+
+fn save_game(save: SaveGame) {
+    save.save();
+}
+
+#[derive(Component, Clone, Copy)]
+enum SlotItem {
+    Slot(usize),
+    Back,
+}
+
+/// A short description of what's in a slot for the menu.
+fn describe_slot(data: Result<Option<SaveData>, String>) -> String {
+    match data {
+        Err(_) => "Unreadable".to_string(),
+        Ok(None) => "Empty".to_string(),
+        Ok(Some(data)) if data.stats.time.is_empty() => {
+            format!("{} levels unlocked", data.records.0.len())
+        }
+        Ok(Some(data)) => {
+            let time = data.stats.time.values().sum::<Duration>();
+            format!("{}, {}", data.level, completion::format_time(time))
+        }
+    }
+}
+
+pub fn spawn_slots_menu(commands: &mut Commands, asset_server: &AssetServer, settings: &Settings) {
+    menu::spawn_menu(commands, asset_server, "Load Game", |items| {
+        for slot in 0..SAVE_SLOTS {
+            let current = if slot == settings.save_slot {
+                " (current)"
+            } else {
+                ""
+            };
+            let label = format!(
+                "Slot {}: {}{}",
+                slot + 1,
+                describe_slot(read_slot(slot)),
+                current
+            );
+
+            items.add(label, true, SlotItem::Slot(slot));
+        }
+
+        items.add("Back", true, SlotItem::Back);
+    });
+}
+
+fn handle_slots_menu(
+    mut events: EventReader<MenuEvent>,
+    items: Query<&SlotItem>,
+    mut stack: ResMut<MenuStack>,
+    mut settings: ResMut<Settings>,
+    mut save: SaveGame,
+) {
+    for action in menu::actions_in(&mut events, MenuScreen::Slots) {
+        match action {
+            MenuAction::Back => {
+                stack.0.pop();
+            }
+            MenuAction::Confirm(item) => match items.get(item) {
+                Ok(SlotItem::Slot(slot)) => {
+                    settings.save_slot = *slot;
+                    save.load(*slot);
+                    stack.0.pop();
+                }
+                Ok(SlotItem::Back) => {
+                    stack.0.pop();
+                }
+                Err(_) => {}
+            },
+            MenuAction::Adjust(..) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn save(version: u32) -> Value {
+        let data = SaveData {
+            version,
+            level: "Test Level 0".to_string(),
+            entrance: None,
+            checkpoint: None,
+            objects: default(),
+            stats: default(),
+            records: default(),
+        };
+        serde_json::to_value(data).unwrap()
+    }
+
+    #[test]
+    fn parses_current_version() {
+        let data = parse_save(&save(SAVE_VERSION).to_string()).unwrap();
+
+        assert_eq!(data.version, SAVE_VERSION);
+        assert_eq!(data.level, "Test Level 0");
+    }
+
+    #[test]
+    fn rejects_missing_version() {
+        let mut value = save(SAVE_VERSION);
+        value.as_object_mut().unwrap().remove("version");
+
+        assert_eq!(
+            parse_save(&value.to_string()).err().as_deref(),
+            Some("it has no version")
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        for version in [0, SAVE_VERSION + 1] {
+            assert_eq!(
+                parse_save(&save(version).to_string()).err(),
+                Some(format!("version {} isn't supported", version))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        assert!(parse_save("{").is_err());
+    }
+
+    #[test]
+    fn runs_migrations_after_the_saves_version() {
+        let migrations: [fn(&mut Value); 2] = [
+            |value| value["first"] = true.into(),
+            |value| value["second"] = true.into(),
+        ];
+
+        let mut value = json!({ "version": 2 });
+        upgrade(&mut value, &migrations).unwrap();
+        assert_eq!(value, json!({ "version": 3, "second": true }));
+
+        let mut value = json!({ "version": 1 });
+        upgrade(&mut value, &migrations).unwrap();
+        assert_eq!(
+            value,
+            json!({ "version": 3, "first": true, "second": true })
+        );
+
+        let mut value = json!({ "version": 3 });
+        upgrade(&mut value, &migrations).unwrap();
+        assert_eq!(value, json!({ "version": 3 }));
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// The file the player's settings are persisted to.
+const CONFIG_FILE: &str = "settings.json";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = config::load::<Settings>(CONFIG_FILE).unwrap_or_default();
+
+        app.insert_resource(settings).add_systems(
+            (save_settings, apply_window_mode).distributive_run_if(resource_changed::<Settings>()),
+        );
+    }
+}
+
+/// Options the player can change from the settings menu.
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// From `0.0` to `1.0`.
+    pub music_volume: f32,
+    /// From `0.0` to `1.0`.
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    /// Draws the game at a low resolution scaled up by whole numbers, so the
+    /// pixel art stays crisp.
+    pub pixel_perfect: bool,
+    /// The save slot that's played from the title screen.
+    pub save_slot: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.8,
+            sfx_volume: 0.8,
+            fullscreen: false,
+            pixel_perfect: true,
+            save_slot: 0,
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    // Don't write out the defaults before anything has been changed.
+    if !settings.is_added() {
+        config::save(CONFIG_FILE, &*settings);
+    }
+}
+
+fn apply_window_mode(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+
+    for mut window in &mut windows {
+        if window.mode != mode {
+            window.mode = mode;
+        }
+    }
+}
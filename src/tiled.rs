@@ -0,0 +1,577 @@
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::loader::LoadErrors;
+
+/// Bits Tiled uses in a tile's gid to store how it's flipped.
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+pub struct TiledPlugin;
+
+impl Plugin for TiledPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<TiledMap>()
+            .add_asset::<TiledTileset>()
+            .init_asset_loader::<TiledMapLoader>()
+            .init_asset_loader::<TiledTilesetLoader>();
+    }
+}
+
+/// A tiled map.
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "8d3e2f0a-6b1c-4f5e-9a7d-3c2b1e0f4a68"]
+pub struct TiledMap {
+    pub height: u32,
+    pub width: u32,
+
+    pub layers: Vec<TiledLayer>,
+
+    #[serde(rename = "nextlayerid")]
+    pub next_layer_id: u32,
+    #[serde(rename = "nextobjectid")]
+    pub next_object_id: u32,
+
+    #[serde(rename = "tileheight")]
+    pub tile_height: u32,
+    #[serde(rename = "tilewidth")]
+    pub tile_width: u32,
+
+    pub tilesets: Vec<Tileset>,
+}
+
+impl TiledMap {
+    /// All the objects in the map's object layers.
+    pub fn objects(&self) -> impl Iterator<Item = &TiledObject> {
+        self.layers
+            .iter()
+            .filter_map(|layer| match layer {
+                TiledLayer::ObjectLayer(layer) => Some(layer.objects.iter()),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Finds the tileset a global tile id belongs to, returning its handle and
+    /// the tile's id local to that tileset.
+    pub fn tileset_for(&self, gid: u32) -> Option<(&Handle<TiledTileset>, u32)> {
+        let gid = gid & !FLIP_FLAGS;
+
+        // Tilesets are sorted by their first gid so the last one that starts
+        // at or before `gid` is the one it belongs to.
+        self.tilesets
+            .iter()
+            .rev()
+            .map(|tileset| match tileset {
+                Tileset::External {
+                    first_gid, handle, ..
+                } => (*first_gid, handle),
+            })
+            .find(|(first_gid, _)| *first_gid <= gid)
+            .map(|(first_gid, handle)| (handle, gid - first_gid))
+    }
+
+    pub fn uses_tileset(&self, handle: &Handle<TiledTileset>) -> bool {
+        self.tilesets.iter().any(|tileset| match tileset {
+            Tileset::External { handle: used, .. } => used == handle,
+        })
+    }
+
+    /// Converts a position in Tiled's pixel coordinates, which start from the
+    /// top left of the map, into a world position.
+    pub fn to_world(&self, x: f32, y: f32) -> Vec2 {
+        let tile_width = self.tile_width as f32;
+        let tile_height = self.tile_height as f32;
+        let height = self.height as f32 * tile_height;
+
+        // Tiles are centered on their position so everything is shifted by
+        // half a tile.
+        Vec2::new(x - tile_width / 2.0, height - y - tile_height / 2.0)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Tileset {
+    // Embedded,
+    External {
+        #[serde(rename = "firstgid")]
+        first_gid: u32,
+        source: String,
+        /// The loaded tileset, filled in by [`TiledMapLoader`].
+        #[serde(skip)]
+        handle: Handle<TiledTileset>,
+    },
+}
+
+/// A layer in a tile map.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum TiledLayer {
+    #[serde(rename = "tilelayer")]
+    TileLayer(TiledTileLayer),
+    #[serde(rename = "objectgroup")]
+    ObjectLayer(TiledObjectLayer),
+    /// Image and group layers, which are skipped so they can still be used
+    /// for reference while editing.
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+pub struct TiledTileLayer {
+    pub id: u32,
+    pub name: String,
+
+    pub data: Vec<u32>,
+
+    pub height: u32,
+    pub width: u32,
+
+    pub opacity: u32,
+    pub visible: bool,
+
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Deserialize)]
+pub struct TiledObjectLayer {
+    pub id: u32,
+    pub name: String,
+
+    pub objects: Vec<TiledObject>,
+}
+
+/// An object placed in an object layer, like a spawn point.
+#[derive(Deserialize)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    /// What kind of object this is. Tiled calls this the object's class but
+    /// still exports it as `type`.
+    #[serde(default, rename = "type", alias = "class")]
+    pub class: String,
+
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default)]
+    pub point: bool,
+
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c1a7e4b2-58d9-4e03-b6f1-7a9e2d5c0b34"]
+pub struct TiledTileset {
+    pub name: String,
+
+    pub columns: u32,
+
+    pub image: String,
+    #[serde(rename = "imageheight")]
+    pub image_height: u32,
+    #[serde(rename = "imagewidth")]
+    pub image_width: u32,
+
+    pub margin: u32,
+    pub spacing: u32,
+
+    #[serde(rename = "tilecount")]
+    pub tile_count: u32,
+    #[serde(rename = "tileheight")]
+    pub tile_height: u32,
+    #[serde(rename = "tilewidth")]
+    pub tile_width: u32,
+
+    #[serde(default)]
+    pub tiles: Vec<TileProperties>,
+
+    /// Every tile in the tileset, indexed by their local tile id. Filled in by
+    /// [`TiledTilesetLoader`].
+    #[serde(skip)]
+    pub atlas: Handle<TextureAtlas>,
+    /// Custom properties keyed by local tile id.
+    #[serde(skip)]
+    properties: HashMap<u32, Vec<Property>>,
+}
+
+impl TiledTileset {
+    /// Gets the custom properties set on the tile with the local id `id`.
+    pub fn tile_properties(&self, id: u32) -> &[Property] {
+        self.properties.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TileProperties {
+    pub id: u32,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Property {
+    pub name: String,
+    /// The property's Tiled type, like `int` or `file`. The value of a `file`
+    /// property on a map object is resolved to an asset path when the map is
+    /// loaded.
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    pub value: Value,
+}
+
+/// A problem with a file reference in a Tiled map or tileset.
+#[derive(Debug)]
+pub struct TiledPathError {
+    /// The asset path of the map or tileset containing the reference.
+    pub file: PathBuf,
+    /// The reference exactly as it's written in the file.
+    pub reference: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for TiledPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` has a bad reference to `{}`: {}",
+            self.file.display(),
+            self.reference,
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for TiledPathError {}
+
+/// Resolves a path referenced from the Tiled file being loaded, which is
+/// relative to that file, into a normalized asset path.
+fn resolve_path(
+    load_context: &LoadContext,
+    reference: &str,
+) -> Result<AssetPath<'static>, TiledPathError> {
+    let file = load_context.path();
+    let error = |reason| TiledPathError {
+        file: file.to_path_buf(),
+        reference: reference.to_string(),
+        reason,
+    };
+
+    let resolved = normalize_reference(file, reference).map_err(error)?;
+
+    // The web build can't check for files, a missing one will fail to load
+    // there instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    if !load_context.asset_io().is_file(&resolved) {
+        return Err(error("the file doesn't exist"));
+    }
+
+    Ok(AssetPath::new(resolved, None))
+}
+
+/// Joins `reference` onto the folder `file` is in, returning why not if it
+/// doesn't point to a file inside the assets folder.
+fn normalize_reference(file: &Path, reference: &str) -> Result<PathBuf, &'static str> {
+    // Maps saved on Windows can use backslashes.
+    let reference_path = reference.replace('\\', "/");
+    let reference_path = Path::new(&reference_path);
+
+    if reference_path.has_root()
+        || reference_path
+            .components()
+            .any(|c| matches!(c, Component::Prefix(_)))
+    {
+        return Err("absolute paths aren't supported, save it relative to the file");
+    }
+
+    let mut resolved = PathBuf::new();
+
+    for component in file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(reference_path)
+        .components()
+    {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err("it points outside of the assets folder");
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => unreachable!(),
+        }
+    }
+
+    if resolved.file_name().is_none() {
+        return Err("it doesn't point to a file");
+    }
+
+    Ok(resolved)
+}
+
+/// Loads maps saved in Tiled's JSON format, along with the tilesets they use.
+struct TiledMapLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for TiledMapLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world
+                .get_resource_or_insert_with(LoadErrors::default)
+                .clone(),
+        }
+    }
+}
+
+impl AssetLoader for TiledMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let result = load_map(bytes, load_context);
+            self.errors.track(load_context.path(), result)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmj"]
+    }
+}
+
+fn load_map(bytes: &[u8], load_context: &mut LoadContext) -> Result<(), bevy::asset::Error> {
+    let mut map = serde_json::from_slice::<TiledMap>(bytes)?;
+    let mut dependencies = vec![];
+
+    if map
+        .layers
+        .iter()
+        .any(|layer| matches!(layer, TiledLayer::Unsupported))
+    {
+        warn!(
+            "`{}` has image or group layers, they won't be shown",
+            load_context.path().display()
+        );
+    }
+
+    for tileset in map.tilesets.iter_mut() {
+        match tileset {
+            Tileset::External { source, handle, .. } => {
+                let path = resolve_path(load_context, source)?;
+
+                *handle = load_context.get_handle(path.clone());
+                dependencies.push(path);
+            }
+        }
+    }
+
+    // Files referenced by objects, like the sounds they play, are relative to
+    // the map too.
+    for layer in map.layers.iter_mut() {
+        let TiledLayer::ObjectLayer(layer) = layer else {
+            continue;
+        };
+
+        for prop in layer
+            .objects
+            .iter_mut()
+            .flat_map(|object| object.properties.iter_mut())
+            .filter(|prop| prop.kind == "file")
+        {
+            let Some(reference) = prop.value.as_str() else {
+                continue;
+            };
+            let path = resolve_path(load_context, reference)?;
+
+            prop.value = Value::String(path.path().to_string_lossy().into_owned());
+            dependencies.push(path);
+        }
+    }
+
+    load_context.set_default_asset(LoadedAsset::new(map).with_dependencies(dependencies));
+    Ok(())
+}
+
+/// Loads tilesets saved in Tiled's JSON format and builds a texture atlas
+/// for their image.
+struct TiledTilesetLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for TiledTilesetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world
+                .get_resource_or_insert_with(LoadErrors::default)
+                .clone(),
+        }
+    }
+}
+
+impl AssetLoader for TiledTilesetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let result = load_tileset(bytes, load_context);
+            self.errors.track(load_context.path(), result)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tsj"]
+    }
+}
+
+fn load_tileset(bytes: &[u8], load_context: &mut LoadContext) -> Result<(), bevy::asset::Error> {
+    let mut tileset = serde_json::from_slice::<TiledTileset>(bytes)?;
+
+    // Image collection tilesets don't have a grid to cut into an atlas.
+    if tileset.columns == 0 || tileset.tile_count == 0 {
+        return Err(bevy::asset::Error::msg(format!(
+            "tileset `{}` has no columns or tiles, only tilesets made from a single image are supported",
+            tileset.name
+        )));
+    }
+
+    let image_path = resolve_path(load_context, &tileset.image)?;
+    let texture = load_context.get_handle(image_path.clone());
+
+    let rows = tileset.tile_count.div_ceil(tileset.columns);
+    let atlas = TextureAtlas::from_grid(
+        texture,
+        Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32),
+        tileset.columns as usize,
+        rows as usize,
+        Some(Vec2::splat(tileset.spacing as f32)),
+        Some(Vec2::splat(tileset.margin as f32)),
+    );
+
+    tileset.atlas = load_context.set_labeled_asset(
+        "atlas",
+        LoadedAsset::new(atlas).with_dependency(image_path.clone()),
+    );
+    tileset.properties = tileset
+        .tiles
+        .iter()
+        .map(|tile| (tile.id, tile.properties.clone()))
+        .collect();
+
+    load_context.set_default_asset(LoadedAsset::new(tileset).with_dependency(image_path));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn skips_unsupported_layers() {
+        let map = json!({
+            "height": 1,
+            "width": 1,
+            "nextlayerid": 4,
+            "nextobjectid": 1,
+            "tileheight": 16,
+            "tilewidth": 16,
+            "tilesets": [],
+            "layers": [
+                {
+                    "type": "tilelayer",
+                    "id": 1,
+                    "name": "base",
+                    "data": [0],
+                    "height": 1,
+                    "width": 1,
+                    "opacity": 1,
+                    "visible": true,
+                    "x": 0,
+                    "y": 0
+                },
+                { "type": "imagelayer", "id": 2, "name": "background", "image": "sky.png" },
+                { "type": "group", "id": 3, "name": "decoration", "layers": [] }
+            ]
+        });
+
+        let map = serde_json::from_value::<TiledMap>(map).unwrap();
+
+        assert!(matches!(map.layers[0], TiledLayer::TileLayer(_)));
+        assert!(matches!(map.layers[1], TiledLayer::Unsupported));
+        assert!(matches!(map.layers[2], TiledLayer::Unsupported));
+    }
+
+    fn normalize(file: &str, reference: &str) -> Result<PathBuf, &'static str> {
+        normalize_reference(Path::new(file), reference)
+    }
+
+    #[test]
+    fn resolves_relative_to_the_file() {
+        assert_eq!(
+            normalize("levels/test_level.tmj", "../tilesets/test.tsj"),
+            Ok(PathBuf::from("tilesets/test.tsj"))
+        );
+        assert_eq!(
+            normalize("tilesets/test.tsj", "./test.png"),
+            Ok(PathBuf::from("tilesets/test.png"))
+        );
+        assert_eq!(
+            normalize("test_level.tmj", "tilesets/test.tsj"),
+            Ok(PathBuf::from("tilesets/test.tsj"))
+        );
+    }
+
+    #[test]
+    fn accepts_backslashes() {
+        assert_eq!(
+            normalize("levels/test_level.tmj", "..\\tilesets\\test.tsj"),
+            Ok(PathBuf::from("tilesets/test.tsj"))
+        );
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert_eq!(
+            normalize("levels/test_level.tmj", "/home/me/test.tsj"),
+            Err("absolute paths aren't supported, save it relative to the file")
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_assets() {
+        assert_eq!(
+            normalize("levels/test_level.tmj", "../../test.tsj"),
+            Err("it points outside of the assets folder")
+        );
+    }
+
+    #[test]
+    fn rejects_paths_to_folders() {
+        assert_eq!(
+            normalize("levels/test_level.tmj", ".."),
+            Err("it doesn't point to a file")
+        );
+    }
+}
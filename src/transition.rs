@@ -0,0 +1,282 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+use serde::Deserialize;
+
+use crate::{level::CurrentLevel, GameState};
+
+/// How long it takes to cover or reveal the screen.
+const TRANSITION_SECONDS: f32 = 0.4;
+/// The size of the generated iris texture.
+const IRIS_TEXTURE_SIZE: u32 = 128;
+const TRANSITION_COLOR: Color = Color::BLACK;
+
+pub struct TransitionPlugin;
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<IrisTexture>()
+            .add_system(start_transition.in_schedule(OnEnter(GameState::Transitioning)))
+            .add_system(reveal.in_schedule(OnEnter(GameState::Playing)))
+            .add_system(reveal.in_schedule(OnEnter(GameState::Complete)))
+            .add_system(animate_transition);
+    }
+}
+
+/// How the screen is covered when moving between levels.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionKind {
+    /// Fade to black.
+    #[default]
+    Fade,
+    /// A circle that closes in on the middle of the screen.
+    Iris,
+    /// Black sliding in from the right and out to the left.
+    Slide,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Phase {
+    Cover,
+    /// Waiting for the next level to be ready.
+    Covered,
+    Reveal,
+}
+
+/// A transition that's playing, the old level is swapped for the new one
+/// while the screen is covered.
+#[derive(Resource)]
+pub struct ScreenTransition {
+    kind: TransitionKind,
+    phase: Phase,
+    timer: Timer,
+    overlay: Entity,
+}
+
+/// Whether the screen has been covered so the level can be swapped.
+pub fn screen_covered(transition: Option<Res<ScreenTransition>>) -> bool {
+    transition.is_some_and(|transition| transition.phase == Phase::Covered)
+}
+
+/// Black with a transparent circle in the middle, scaled down to close the
+/// iris.
+#[derive(Resource)]
+struct IrisTexture(Handle<Image>);
+
+impl FromWorld for IrisTexture {
+    fn from_world(world: &mut World) -> Self {
+        let size = IRIS_TEXTURE_SIZE;
+        let radius = size as f32 / 2.0;
+        let [r, g, b, _] = TRANSITION_COLOR
+            .as_rgba_f32()
+            .map(|channel| (channel * 255.0) as u8);
+
+        let data = (0..size * size)
+            .flat_map(|i| {
+                let x = (i % size) as f32 + 0.5 - radius;
+                let y = (i / size) as f32 + 0.5 - radius;
+                let alpha = if x * x + y * y > radius * radius {
+                    255
+                } else {
+                    0
+                };
+                [r, g, b, alpha]
+            })
+            .collect();
+
+        let mut image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        // Images default to nearest sampling for the pixel art, but the iris
+        // is stretched over the whole screen and would look blocky.
+        image.sampler_descriptor = ImageSampler::linear();
+
+        Self(world.resource_mut::<Assets<Image>>().add(image))
+    }
+}
+
+/// Parts of the overlay that are sized to animate it.
+#[derive(Component)]
+enum OverlayPart {
+    /// The whole overlay, faded or slid.
+    Panel,
+    /// The middle row holding the iris, sized with it.
+    IrisRow,
+    Iris,
+}
+
+fn start_transition(
+    mut commands: Commands,
+    current: Res<CurrentLevel>,
+    iris: Res<IrisTexture>,
+    windows: Query<&Window>,
+    previous: Option<Res<ScreenTransition>>,
+) {
+    // Leaving a level while the last transition is still revealing it.
+    if let Some(previous) = previous {
+        commands.entity(previous.overlay).despawn_recursive();
+    }
+
+    let kind = current
+        .taken_exit()
+        .map(|exit| exit.transition)
+        .unwrap_or_default();
+
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::all(Val::Percent(100.0)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        // Drawn above the level but below the loading screen.
+        z_index: ZIndex::Global(90),
+        ..default()
+    };
+
+    let overlay = match kind {
+        TransitionKind::Fade => commands.spawn((OverlayPart::Panel, root)).id(),
+        TransitionKind::Slide => {
+            let mut panel = root;
+            panel.style.position.left = Val::Percent(100.0);
+            panel.background_color = TRANSITION_COLOR.into();
+
+            commands.spawn((OverlayPart::Panel, panel)).id()
+        }
+        TransitionKind::Iris => {
+            let window = windows.single();
+            // Start big enough that the circle is outside the window.
+            let size = window.width().hypot(window.height());
+
+            let panel = || NodeBundle {
+                style: Style {
+                    flex_grow: 1.0,
+                    ..default()
+                },
+                background_color: TRANSITION_COLOR.into(),
+                ..default()
+            };
+
+            commands
+                .spawn(root)
+                .with_children(|parent| {
+                    parent.spawn(panel());
+                    parent
+                        .spawn((
+                            OverlayPart::IrisRow,
+                            NodeBundle {
+                                style: Style {
+                                    size: Size::height(Val::Px(size)),
+                                    flex_shrink: 0.0,
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(panel());
+                            parent.spawn((
+                                OverlayPart::Iris,
+                                ImageBundle {
+                                    style: Style {
+                                        size: Size::all(Val::Px(size)),
+                                        flex_shrink: 0.0,
+                                        ..default()
+                                    },
+                                    image: iris.0.clone().into(),
+                                    ..default()
+                                },
+                            ));
+                            parent.spawn(panel());
+                        });
+                    parent.spawn(panel());
+                })
+                .id()
+        }
+    };
+
+    commands.insert_resource(ScreenTransition {
+        kind,
+        phase: Phase::Cover,
+        timer: Timer::from_seconds(TRANSITION_SECONDS, TimerMode::Once),
+        overlay,
+    });
+}
+
+/// Starts uncovering the screen once the next level has been spawned.
+fn reveal(transition: Option<ResMut<ScreenTransition>>) {
+    if let Some(mut transition) = transition {
+        if transition.phase == Phase::Covered {
+            transition.phase = Phase::Reveal;
+            transition.timer.reset();
+        }
+    }
+}
+
+fn animate_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    transition: Option<ResMut<ScreenTransition>>,
+    windows: Query<&Window>,
+    mut parts: Query<(&OverlayPart, &mut Style, &mut BackgroundColor)>,
+) {
+    let Some(mut transition) = transition else {
+        return;
+    };
+
+    transition.timer.tick(time.delta());
+
+    // How much of the screen is covered, from 0 to 1.
+    let coverage = match transition.phase {
+        Phase::Cover => transition.timer.percent(),
+        Phase::Covered => 1.0,
+        Phase::Reveal => 1.0 - transition.timer.percent(),
+    };
+
+    for (part, mut style, mut background) in &mut parts {
+        match (transition.kind, part) {
+            (TransitionKind::Fade, OverlayPart::Panel) => {
+                background.0 = TRANSITION_COLOR.with_a(coverage);
+            }
+            (TransitionKind::Slide, OverlayPart::Panel) => {
+                // Slides in from the right and carries on out to the left.
+                let offset = match transition.phase {
+                    Phase::Reveal => coverage - 1.0,
+                    _ => 1.0 - coverage,
+                };
+                style.position.left = Val::Percent(offset * 100.0);
+            }
+            (TransitionKind::Iris, OverlayPart::IrisRow | OverlayPart::Iris) => {
+                let window = windows.single();
+                let size = window.width().hypot(window.height()) * (1.0 - coverage);
+
+                style.size.height = Val::Px(size);
+                if matches!(part, OverlayPart::Iris) {
+                    style.size.width = Val::Px(size);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match transition.phase {
+        Phase::Cover if transition.timer.finished() => transition.phase = Phase::Covered,
+        Phase::Reveal if transition.timer.finished() => {
+            commands.entity(transition.overlay).despawn_recursive();
+            commands.remove_resource::<ScreenTransition>();
+        }
+        _ => {}
+    }
+}